@@ -0,0 +1,177 @@
+use {
+    crate::{error::Error, options::Options},
+    std::sync::{Arc, Mutex, PoisonError},
+};
+
+// the graphics apis adapters are looked for on
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Backend {
+    All,
+    Vulkan,
+    Metal,
+    Dx12,
+    Gl,
+}
+
+impl std::str::FromStr for Backend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "all" => Ok(Backend::All),
+            "vulkan" => Ok(Backend::Vulkan),
+            "metal" => Ok(Backend::Metal),
+            "dx12" => Ok(Backend::Dx12),
+            "gl" => Ok(Backend::Gl),
+            _ => Err(format!("unknown backend {}", s)),
+        }
+    }
+}
+
+impl Backend {
+    fn backends(self) -> wgpu::Backends {
+        match self {
+            Backend::All => wgpu::Backends::all(),
+            Backend::Vulkan => wgpu::Backends::VULKAN,
+            Backend::Metal => wgpu::Backends::METAL,
+            Backend::Dx12 => wgpu::Backends::DX12,
+            Backend::Gl => wgpu::Backends::GL,
+        }
+    }
+}
+
+pub fn instance(options: &Options) -> wgpu::Instance {
+    wgpu::Instance::new(wgpu::InstanceDescriptor {
+        backends: options.backend.backends(),
+        dx12_shader_compiler: Default::default(),
+    })
+}
+
+fn describe(index: usize, adapter: &wgpu::Adapter) -> String {
+    let info: wgpu::AdapterInfo = adapter.get_info();
+    format!(
+        "{}: {} ({:?}, {:?})",
+        index, info.name, info.backend, info.device_type
+    )
+}
+
+// the adapters on the chosen backends, for --adapters
+pub fn list(options: &Options) -> Vec<String> {
+    instance(options)
+        .enumerate_adapters(options.backend.backends())
+        .enumerate()
+        .map(|(i, adapter)| describe(i, &adapter))
+        .collect()
+}
+
+// gpus before software adapters
+fn rank(device_type: wgpu::DeviceType) -> u32 {
+    match device_type {
+        wgpu::DeviceType::DiscreteGpu => 0,
+        wgpu::DeviceType::IntegratedGpu => 1,
+        wgpu::DeviceType::VirtualGpu => 2,
+        wgpu::DeviceType::Other => 3,
+        wgpu::DeviceType::Cpu => 4,
+    }
+}
+
+// the best adapter that can present to `surface`, if any, of the
+// ones --adapter names by index or part of the name, and only software ones with
+// --fallback; the error lists every adapter and why it wasn't picked
+pub fn select(
+    instance: &wgpu::Instance,
+    options: &Options,
+    surface: Option<&wgpu::Surface>,
+) -> Result<wgpu::Adapter, Error> {
+    let mut suitable: Vec<wgpu::Adapter> = Vec::new();
+    let mut rejected: Vec<String> = Vec::new();
+    for (i, adapter) in instance
+        .enumerate_adapters(options.backend.backends())
+        .enumerate()
+    {
+        let info: wgpu::AdapterInfo = adapter.get_info();
+        let reason: Option<String> = match &options.adapter {
+            Some(name)
+                if *name != i.to_string()
+                    && !info.name.to_lowercase().contains(&name.to_lowercase()) =>
+            {
+                Some(format!("not {}", name))
+            }
+            _ if options.fallback && info.device_type != wgpu::DeviceType::Cpu => {
+                Some("not a software adapter".to_string())
+            }
+            _ if surface.is_some_and(|surface| !adapter.is_surface_supported(surface)) => {
+                Some("can't present to the window".to_string())
+            }
+            _ => None,
+        };
+        match reason {
+            Some(reason) => rejected.push(format!("  {}: {}", describe(i, &adapter), reason)),
+            None => suitable.push(adapter),
+        }
+    }
+    if rejected.is_empty() && suitable.is_empty() {
+        return Err(Error::Init(format!(
+            "no gpu adapters found on {:?}",
+            options.backend
+        )));
+    }
+    suitable
+        .into_iter()
+        .min_by_key(|adapter| rank(adapter.get_info().device_type))
+        .ok_or_else(|| Error::Init(format!("no suitable gpu adapter:\n{}", rejected.join("\n"))))
+}
+
+// the first error of the device's since the last check, which wgpu would otherwise
+// panic on, out of memory or validation failing on a lost device
+#[derive(Clone, Debug, Default)]
+pub struct Failures(Arc<Mutex<Option<String>>>);
+
+impl Failures {
+    pub fn check(&self) -> Result<(), Error> {
+        match self.0.lock().unwrap_or_else(PoisonError::into_inner).take() {
+            Some(e) => Err(Error::Device(e)),
+            None => Ok(()),
+        }
+    }
+}
+
+// a device with no features and the default limits, but for the texture sizes and
+// compute workgroups the adapter allows, so --workgroup-size can go past 256
+pub async fn device(
+    adapter: &wgpu::Adapter,
+) -> Result<(wgpu::Device, wgpu::Queue, Failures), Error> {
+    let limits: wgpu::Limits = if cfg!(target_arch = "wasm32") {
+        wgpu::Limits::downlevel_webgl2_defaults()
+    } else {
+        wgpu::Limits::default()
+    };
+    let supported: wgpu::Limits = adapter.limits();
+    let (device, queue) = adapter
+        .request_device(
+            &wgpu::DeviceDescriptor {
+                label: None,
+                features: wgpu::Features::empty(),
+                limits: wgpu::Limits {
+                    max_compute_invocations_per_workgroup: supported
+                        .max_compute_invocations_per_workgroup,
+                    max_compute_workgroup_size_x: supported.max_compute_workgroup_size_x,
+                    max_compute_workgroup_storage_size: supported
+                        .max_compute_workgroup_storage_size,
+                    ..limits.using_resolution(supported.clone())
+                },
+            },
+            None,
+        )
+        .await
+        .map_err(|e| Error::Init(format!("{}: {}", adapter.get_info().name, e)))?;
+    let failures: Failures = Failures::default();
+    let first: Arc<Mutex<Option<String>>> = failures.0.clone();
+    device.on_uncaptured_error(Box::new(move |e| {
+        first
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get_or_insert_with(|| e.to_string());
+    }));
+    Ok((device, queue, failures))
+}
@@ -0,0 +1,441 @@
+use {
+    crate::{error::Error, GpuInfo, Particle},
+    std::{
+        ops::Range,
+        sync::{Arc, Mutex, PoisonError},
+    },
+    wgpu::util::DeviceExt,
+};
+
+// two storage buffers the compute shader ping-pongs between: bind group k reads
+// buffers[k] as the old state and writes buffers[1 - k], so no copy is needed per step
+pub struct ParticleBuffers {
+    pub count: usize,
+    pub buffers: [wgpu::Buffer; 2],
+    pub bind_group_layout: wgpu::BindGroupLayout,
+    pub bind_groups: [wgpu::BindGroup; 2],
+    // binding 2 read only, for everything but the compute step; draw bind group k
+    // binds buffers[k]
+    pub draw_layout: wgpu::BindGroupLayout,
+    pub draw_bind_groups: [wgpu::BindGroup; 2],
+    // index of the buffer holding the latest state
+    pub current: usize,
+}
+
+fn create_buffers(device: &wgpu::Device, particles: &[Particle]) -> [wgpu::Buffer; 2] {
+    let usage: wgpu::BufferUsages = wgpu::BufferUsages::COPY_SRC
+        | wgpu::BufferUsages::COPY_DST
+        | wgpu::BufferUsages::STORAGE;
+    [
+        device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Particle Buffer 0"),
+            contents: bytemuck::cast_slice(particles),
+            usage,
+        }),
+        device.create_buffer(&wgpu::BufferDescriptor {
+            size: std::mem::size_of_val(particles) as u64,
+            usage,
+            label: Some("Particle Buffer 1"),
+            mapped_at_creation: false,
+        }),
+    ]
+}
+
+fn create_bind_groups(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    gpu_buffer: &wgpu::Buffer,
+    buffers: &[wgpu::Buffer; 2],
+) -> [wgpu::BindGroup; 2] {
+    [0, 1].map(|k| {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Bind Group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: gpu_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: buffers[k].as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: buffers[1 - k].as_entire_binding(),
+                },
+            ],
+        })
+    })
+}
+
+fn create_draw_bind_groups(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    gpu_buffer: &wgpu::Buffer,
+    buffers: &[wgpu::Buffer; 2],
+) -> [wgpu::BindGroup; 2] {
+    [0, 1].map(|k| {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Draw Bind Group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: gpu_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: buffers[k].as_entire_binding(),
+                },
+            ],
+        })
+    })
+}
+
+// the uniform and one particle buffer, which the compute step writes and the
+// draw layout only reads
+fn layout_entries(
+    visibility: wgpu::ShaderStages,
+    read_only: bool,
+) -> [wgpu::BindGroupLayoutEntry; 2] {
+    [
+        wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: wgpu::BufferSize::new(size_of::<GpuInfo>() as _),
+            },
+            count: None,
+        },
+        wgpu::BindGroupLayoutEntry {
+            binding: 2,
+            visibility,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: wgpu::BufferSize::new(size_of::<Particle>() as _),
+            },
+            count: None,
+        },
+    ]
+}
+
+impl ParticleBuffers {
+    // `visibility` is added to the compute stage for the draw layout
+    pub fn new(
+        device: &wgpu::Device,
+        gpu_buffer: &wgpu::Buffer,
+        particles: &[Particle],
+        visibility: wgpu::ShaderStages,
+    ) -> Self {
+        let buffers: [wgpu::Buffer; 2] = create_buffers(device, particles);
+        let [info, current] = layout_entries(wgpu::ShaderStages::COMPUTE, false);
+        let bind_group_layout: wgpu::BindGroupLayout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Bind Group Layout"),
+                entries: &[
+                    info,
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: true },
+                            has_dynamic_offset: false,
+                            min_binding_size: wgpu::BufferSize::new(size_of::<Particle>() as _),
+                        },
+                        count: None,
+                    },
+                    current,
+                ],
+            });
+        let bind_groups: [wgpu::BindGroup; 2] =
+            create_bind_groups(device, &bind_group_layout, gpu_buffer, &buffers);
+        let draw_layout: wgpu::BindGroupLayout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Draw Bind Group Layout"),
+                entries: &layout_entries(wgpu::ShaderStages::COMPUTE | visibility, true),
+            });
+        let draw_bind_groups: [wgpu::BindGroup; 2] =
+            create_draw_bind_groups(device, &draw_layout, gpu_buffer, &buffers);
+        Self {
+            count: particles.len(),
+            buffers,
+            bind_group_layout,
+            bind_groups,
+            draw_layout,
+            draw_bind_groups,
+            current: 0,
+        }
+    }
+
+    // latest particle state
+    pub fn cur(&self) -> &wgpu::Buffer {
+        &self.buffers[self.current]
+    }
+
+    // the bind group for the next compute step, call swap() once it is recorded
+    pub fn step_bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_groups[self.current]
+    }
+
+    pub fn swap(&mut self) {
+        self.current = 1 - self.current;
+    }
+
+    // the draw bind group of the latest state
+    pub fn draw_bind_group(&self) -> &wgpu::BindGroup {
+        &self.draw_bind_groups[self.current]
+    }
+
+    // replaces the simulated particles, recreating the buffers if the count changed
+    pub fn upload(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        gpu_buffer: &wgpu::Buffer,
+        particles: &[Particle],
+    ) {
+        if particles.len() == self.count {
+            queue.write_buffer(self.cur(), 0, bytemuck::cast_slice(particles));
+            return;
+        }
+        self.buffers = create_buffers(device, particles);
+        self.bind_groups =
+            create_bind_groups(device, &self.bind_group_layout, gpu_buffer, &self.buffers);
+        self.draw_bind_groups =
+            create_draw_bind_groups(device, &self.draw_layout, gpu_buffer, &self.buffers);
+        self.count = particles.len();
+        self.current = 0;
+    }
+
+    // copies the current buffer back to the cpu, failing when the device is lost
+    pub async fn read(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> Result<Vec<Particle>, Error> {
+        self.read_range(device, queue, 0..self.count).await
+    }
+
+    // copies the particles in `range` of the current buffer back to the cpu
+    pub async fn read_range(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        range: Range<usize>,
+    ) -> Result<Vec<Particle>, Error> {
+        let offset: u64 = (range.start * size_of::<Particle>()) as u64;
+        let size: u64 = (range.len() * size_of::<Particle>()) as u64;
+        let staging: wgpu::Buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            label: Some("Readback Buffer"),
+            mapped_at_creation: false,
+        });
+        let mut encoder: wgpu::CommandEncoder =
+            device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Readback Encoder"),
+            });
+        encoder.copy_buffer_to_buffer(self.cur(), offset, &staging, 0, size);
+        queue.submit([encoder.finish()]);
+
+        let slice: wgpu::BufferSlice<'_> = staging.slice(..);
+        let (sender, receiver) = futures::channel::oneshot::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        device.poll(wgpu::Maintain::Wait);
+        receiver
+            .await
+            .map_err(|_| Error::Device("readback buffer was never mapped".into()))?
+            .map_err(|e| Error::Device(format!("failed to map readback buffer: {}", e)))?;
+        let particles: Vec<Particle> = bytemuck::cast_slice(&slice.get_mapped_range()).to_vec();
+        staging.unmap();
+        Ok(particles)
+    }
+}
+
+// particles copied back without waiting on the gpu: `copy` records the copy into
+// a frame's encoder, `map` asks for it once that frame is submitted and `take`
+// hands it to a later frame once it is mapped
+pub struct Readback {
+    staging: wgpu::Buffer,
+    // the most particles one copy holds
+    capacity: usize,
+    // the particles being copied, until they are taken
+    range: Option<Range<usize>>,
+    mapping: bool,
+    // set by the map callback
+    mapped: Arc<Mutex<Option<Result<(), wgpu::BufferAsyncError>>>>,
+}
+
+impl Readback {
+    pub fn new(device: &wgpu::Device, capacity: usize) -> Self {
+        let staging: wgpu::Buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            size: (capacity * size_of::<Particle>()) as u64,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            label: Some("Particle Readback Buffer"),
+            mapped_at_creation: false,
+        });
+        Self {
+            staging,
+            capacity,
+            range: None,
+            mapping: false,
+            mapped: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    // whether a copy is on its way, `copy` does nothing until it is taken
+    pub fn busy(&self) -> bool {
+        self.range.is_some()
+    }
+
+    pub fn copy(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        buffers: &ParticleBuffers,
+        range: Range<usize>,
+    ) {
+        if self.busy()
+            || range.is_empty()
+            || range.end > buffers.count
+            || range.len() > self.capacity
+        {
+            return;
+        }
+        let offset: u64 = (range.start * size_of::<Particle>()) as u64;
+        let size: u64 = (range.len() * size_of::<Particle>()) as u64;
+        encoder.copy_buffer_to_buffer(buffers.cur(), offset, &self.staging, 0, size);
+        self.range = Some(range);
+    }
+
+    // after the encoder `copy` recorded into is submitted
+    pub fn map(&mut self) {
+        if self.range.is_none() || self.mapping {
+            return;
+        }
+        self.mapping = true;
+        let mapped: Arc<Mutex<Option<Result<(), wgpu::BufferAsyncError>>>> = self.mapped.clone();
+        self.staging
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                *mapped.lock().unwrap_or_else(PoisonError::into_inner) = Some(result);
+            });
+    }
+
+    // the copied particles and the index of the first, once mapped
+    pub fn take(&mut self, device: &wgpu::Device) -> Result<Option<(usize, Vec<Particle>)>, Error> {
+        device.poll(wgpu::Maintain::Poll);
+        let mapped: Option<Result<(), wgpu::BufferAsyncError>> = self
+            .mapped
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+        let Some(result) = mapped else {
+            return Ok(None);
+        };
+        self.mapping = false;
+        let range: Option<Range<usize>> = self.range.take();
+        result.map_err(|e| Error::Device(format!("failed to map readback buffer: {}", e)))?;
+        let Some(range) = range else {
+            return Ok(None);
+        };
+        let size: usize = range.len() * size_of::<Particle>();
+        let particles: Vec<Particle> =
+            bytemuck::cast_slice(&self.staging.slice(..).get_mapped_range()[..size]).to_vec();
+        self.staging.unmap();
+        Ok(Some((range.start, particles)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        std::mem::{offset_of, size_of},
+    };
+
+    // checks that the Particle struct of a shader matches the Rust one byte for byte
+    fn validate_layout(source: &str) -> Result<(), String> {
+        let module: naga::Module =
+            naga::front::wgsl::parse_str(source).map_err(|e| e.emit_to_string(source))?;
+        let (members, span) = module
+            .types
+            .iter()
+            .find_map(|(_, ty)| match (&ty.name, &ty.inner) {
+                (Some(name), naga::TypeInner::Struct { members, span }) if name == "Particle" => {
+                    Some((members, *span))
+                }
+                _ => None,
+            })
+            .ok_or("no Particle struct")?;
+        if span as usize != size_of::<Particle>() {
+            return Err(format!(
+                "Particle is {} bytes, expected {}",
+                span,
+                size_of::<Particle>()
+            ));
+        }
+        let fields: [(&str, usize); 6] = [
+            ("pos", offset_of!(Particle, pos)),
+            ("component", offset_of!(Particle, component)),
+            ("vel", offset_of!(Particle, vel)),
+            ("id", offset_of!(Particle, id)),
+            ("mass", offset_of!(Particle, mass)),
+            ("calibrate", offset_of!(Particle, calibrate)),
+        ];
+        for (field, offset) in fields {
+            let member: &naga::StructMember = members
+                .iter()
+                .find(|m| m.name.as_deref() == Some(field))
+                .ok_or_else(|| format!("Particle has no field {}", field))?;
+            if member.offset as usize != offset {
+                return Err(format!(
+                    "Particle.{} is at offset {}, expected {}",
+                    field, member.offset, offset
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn shaders_match_particle() {
+        let shaders: [(&str, String); 6] = [
+            (
+                "compute.wgsl",
+                crate::compute::source(256, crate::compute::Integrator::Leapfrog),
+            ),
+            ("draw.wgsl", include_str!("shaders/draw.wgsl").into()),
+            ("density.wgsl", include_str!("shaders/density.wgsl").into()),
+            (
+                "projection.wgsl",
+                include_str!("shaders/projection.wgsl").into(),
+            ),
+            ("overlay.wgsl", include_str!("shaders/overlay.wgsl").into()),
+            ("trail.wgsl", include_str!("shaders/trail.wgsl").into()),
+        ];
+        for (name, source) in shaders {
+            if let Err(e) = validate_layout(&source) {
+                panic!("{} does not match Particle: {}", name, e);
+            }
+        }
+    }
+
+    // the shaders without particles only need to parse
+    #[test]
+    fn shaders_parse() {
+        for (name, source) in [
+            ("post.wgsl", include_str!("shaders/post.wgsl")),
+            ("hud.wgsl", include_str!("shaders/hud.wgsl")),
+        ] {
+            if let Err(e) = naga::front::wgsl::parse_str(source) {
+                panic!("{}: {}", name, e.emit_to_string(source));
+            }
+        }
+    }
+}
@@ -0,0 +1,115 @@
+use crate::buffers::ParticleBuffers;
+
+pub const DEFAULT_WORKGROUP_SIZE: u32 = 256;
+
+// how a step moves the particles after the direct-sum force
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Integrator {
+    // kick then drift, what headless runs, sweeps and offscreen frames use
+    Euler,
+    // drift-kick-drift, a step with -motion undoes one with motion
+    Leapfrog,
+}
+
+impl Integrator {
+    // shown on the hud
+    pub fn method(self) -> &'static str {
+        match self {
+            Integrator::Euler => "direct sum, semi-implicit euler",
+            Integrator::Leapfrog => "direct sum, leapfrog",
+        }
+    }
+
+    // the part of a step's drift taken before the kick
+    fn leading_drift(self) -> &'static str {
+        match self {
+            Integrator::Euler => "0.0",
+            Integrator::Leapfrog => "0.5",
+        }
+    }
+}
+
+// the direct-sum force kernel, built for one workgroup size
+pub struct Kernel {
+    pub pipeline: wgpu::ComputePipeline,
+    pub workgroup_size: u32,
+    pub integrator: Integrator,
+}
+
+pub fn source(workgroup_size: u32, integrator: Integrator) -> String {
+    include_str!("shaders/compute.wgsl")
+        .replace("WORKGROUP_SIZE", &format!("{}u", workgroup_size))
+        .replace("LEADING_DRIFT", integrator.leading_drift())
+}
+
+impl Kernel {
+    // `workgroup_size` is clamped to what the device allows
+    pub fn new(
+        device: &wgpu::Device,
+        bind_group_layout: &wgpu::BindGroupLayout,
+        workgroup_size: u32,
+        integrator: Integrator,
+    ) -> Self {
+        let limits: wgpu::Limits = device.limits();
+        let limit: u32 = limits
+            .max_compute_invocations_per_workgroup
+            .min(limits.max_compute_workgroup_size_x)
+            // a vec4 and an f32 of workgroup memory per invocation
+            .min(limits.max_compute_workgroup_storage_size / 20);
+        let workgroup_size: u32 = if workgroup_size == 0 || workgroup_size > limit {
+            eprintln!(
+                "workgroup size {} is not supported by the device, using {}",
+                workgroup_size, limit
+            );
+            limit
+        } else {
+            workgroup_size
+        };
+
+        let source: String = source(workgroup_size, integrator);
+        let cs_mod: wgpu::ShaderModule =
+            device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("Compute Shader"),
+                source: wgpu::ShaderSource::Wgsl(source.into()),
+            });
+        let pipeline_layout: wgpu::PipelineLayout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Compute Pipeline Layout"),
+                bind_group_layouts: &[bind_group_layout],
+                push_constant_ranges: &[],
+            });
+        let pipeline: wgpu::ComputePipeline =
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some("Compute Pipeline"),
+                module: &cs_mod,
+                entry_point: "main",
+                layout: Some(&pipeline_layout),
+            });
+        Self {
+            pipeline,
+            workgroup_size,
+            integrator,
+        }
+    }
+
+    // records `steps` steps, swapping the particle buffers after each
+    pub fn step(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        buffers: &mut ParticleBuffers,
+        steps: u32,
+    ) {
+        let workgroups: u32 = (buffers.count as u32).div_ceil(self.workgroup_size);
+        for _ in 0..steps {
+            let mut cpass: wgpu::ComputePass<'_> =
+                encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                    label: Some("Compute Pass"),
+                });
+            cpass.set_pipeline(&self.pipeline);
+            cpass.set_bind_group(0, buffers.step_bind_group(), &[]);
+            cpass.dispatch_workgroups(workgroups, 1, 1);
+            drop(cpass);
+            buffers.swap();
+        }
+    }
+}
@@ -0,0 +1,161 @@
+use {
+    crate::{Particle, G},
+    serde::Serialize,
+    std::{
+        collections::BTreeMap,
+        f64::consts::FRAC_PI_2,
+        sync::mpsc::{self, Receiver, Sender},
+        thread,
+    },
+};
+
+// mass and bulk motion of the particles generated from one galaxy
+#[derive(Serialize, Clone, Copy, Debug, Default)]
+pub struct ComponentDiagnostics {
+    pub component: u32,
+    pub count: usize,
+    pub mass: f64,
+    pub center_of_mass: [f64; 3],
+    pub velocity: [f64; 3],
+}
+
+// conserved quantities of a particle set, accumulated in f64
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct Diagnostics {
+    pub step: u64,
+    pub time: f64,
+    pub kinetic: f64,
+    pub potential: f64,
+    pub energy: f64,
+    pub momentum: [f64; 3],
+    pub angular_momentum: [f64; 3],
+    pub center_of_mass: [f64; 3],
+    pub components: Vec<ComponentDiagnostics>,
+}
+
+fn to_f64(v: [f32; 3]) -> [f64; 3] {
+    [v[0] as f64, v[1] as f64, v[2] as f64]
+}
+
+impl Diagnostics {
+    pub fn new(particles: &[Particle], step: u64, time: f64) -> Self {
+        let mut out: Diagnostics = Diagnostics {
+            step,
+            time,
+            ..Default::default()
+        };
+        let mut total_mass: f64 = 0.0;
+        let mut components: BTreeMap<u32, ComponentDiagnostics> = BTreeMap::new();
+        for p in particles {
+            let (m, pos, vel) = (p.mass as f64, to_f64(p.pos), to_f64(p.vel));
+            out.kinetic += 0.5 * m * (vel[0] * vel[0] + vel[1] * vel[1] + vel[2] * vel[2]);
+            for k in 0..3 {
+                out.momentum[k] += m * vel[k];
+                out.center_of_mass[k] += m * pos[k];
+            }
+            out.angular_momentum[0] += m * (pos[1] * vel[2] - pos[2] * vel[1]);
+            out.angular_momentum[1] += m * (pos[2] * vel[0] - pos[0] * vel[2]);
+            out.angular_momentum[2] += m * (pos[0] * vel[1] - pos[1] * vel[0]);
+            total_mass += m;
+
+            let c: &mut ComponentDiagnostics = components.entry(p.component).or_default();
+            c.count += 1;
+            c.mass += m;
+            for k in 0..3 {
+                c.center_of_mass[k] += m * pos[k];
+                c.velocity[k] += m * vel[k];
+            }
+        }
+        if total_mass > 0.0 {
+            out.center_of_mass = out.center_of_mass.map(|c| c / total_mass);
+        }
+        out.components = components
+            .into_iter()
+            .map(|(component, mut c)| {
+                c.component = component;
+                if c.mass > 0.0 {
+                    c.center_of_mass = c.center_of_mass.map(|x| x / c.mass);
+                    c.velocity = c.velocity.map(|v| v / c.mass);
+                }
+                c
+            })
+            .collect();
+
+        // pairwise potential of the force in compute.wgsl, m / (r^2 + calibrate)
+        for (i, a) in particles.iter().enumerate() {
+            let pos_a: [f64; 3] = to_f64(a.pos);
+            let mut sum: f64 = 0.0;
+            for b in &particles[i + 1..] {
+                let pos_b: [f64; 3] = to_f64(b.pos);
+                let d: [f64; 3] = [
+                    pos_b[0] - pos_a[0],
+                    pos_b[1] - pos_a[1],
+                    pos_b[2] - pos_a[2],
+                ];
+                let r: f64 = (d[0] * d[0] + d[1] * d[1] + d[2] * d[2]).sqrt();
+                let c: f64 = (b.calibrate as f64).sqrt();
+                sum += b.mass as f64 / c * (FRAC_PI_2 - (r / c).atan());
+            }
+            out.potential -= G as f64 * a.mass as f64 * sum;
+        }
+        out.energy = out.kinetic + out.potential;
+        out
+    }
+
+    // relative change in total energy since `initial`
+    pub fn drift(&self, initial: &Diagnostics) -> f64 {
+        if initial.energy == 0.0 {
+            return 0.0;
+        }
+        (self.energy - initial.energy) / initial.energy.abs()
+    }
+}
+
+// the energy drift of particle sets measured on a thread of its own, since the
+// pairwise sum would stall whoever waits on it
+pub struct DriftMeter {
+    samples: Sender<Vec<Particle>>,
+    drifts: Receiver<f64>,
+    // a sample was sent and its drift not yet received
+    busy: bool,
+}
+
+impl DriftMeter {
+    // the drift is measured from `initial`
+    pub fn new(initial: Vec<Particle>) -> Self {
+        let (samples, received): (Sender<Vec<Particle>>, Receiver<Vec<Particle>>) = mpsc::channel();
+        let (measured, drifts): (Sender<f64>, Receiver<f64>) = mpsc::channel();
+        // ends once the meter is dropped
+        thread::spawn(move || {
+            let initial: Diagnostics = Diagnostics::new(&initial, 0, 0.0);
+            for particles in received {
+                let drift: f64 = Diagnostics::new(&particles, 0, 0.0).drift(&initial);
+                if measured.send(drift).is_err() {
+                    break;
+                }
+            }
+        });
+        Self {
+            samples,
+            drifts,
+            busy: false,
+        }
+    }
+
+    pub fn busy(&self) -> bool {
+        self.busy
+    }
+
+    pub fn measure(&mut self, particles: Vec<Particle>) {
+        self.busy = self.samples.send(particles).is_ok();
+    }
+
+    // the drift of the last sample once it is measured
+    pub fn drift(&mut self) -> Option<f64> {
+        let drift: Option<f64> = self.drifts.try_recv().ok();
+        if drift.is_some() {
+            self.busy = false;
+        }
+        drift
+    }
+}
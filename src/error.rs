@@ -0,0 +1,64 @@
+use {
+    crate::{options::USAGE, scenario::ScenarioError, sweep::SweepError},
+    std::{fmt, io, path::PathBuf},
+};
+
+// everything a run can fail with, handed back to main, which prints it and exits
+// with its code so scripts can tell the kinds apart
+#[derive(Debug)]
+pub enum Error {
+    // a bad command line, exits with 2
+    Usage(String),
+    // scenario and sweep files that don't load or parse, 3
+    Scenario(ScenarioError),
+    Sweep(SweepError),
+    // reading or writing the file or directory, 4
+    Io(PathBuf, io::Error),
+    // creating the window, surface, adapter or device, 5
+    Init(String),
+    // the device failing or being lost while running, 6
+    Device(String),
+}
+
+impl Error {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Usage(_) => 2,
+            Error::Scenario(_) => 3,
+            // a sweep's runs fail like any other
+            Error::Sweep(SweepError::Run(_, e)) => e.exit_code(),
+            Error::Sweep(SweepError::Io(..)) => 4,
+            Error::Sweep(_) => 3,
+            Error::Io(..) => 4,
+            Error::Init(_) => 5,
+            Error::Device(_) => 6,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Usage(e) => write!(f, "{}\n{}", e, USAGE),
+            Error::Scenario(e) => write!(f, "failed to load scenario {}", e),
+            Error::Sweep(e) => write!(f, "sweep failed: {}", e),
+            Error::Io(path, e) => write!(f, "{}: {}", path.display(), e),
+            Error::Init(e) => write!(f, "failed to start: {}", e),
+            Error::Device(e) => write!(f, "gpu device failed: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<ScenarioError> for Error {
+    fn from(e: ScenarioError) -> Self {
+        Error::Scenario(e)
+    }
+}
+
+impl From<SweepError> for Error {
+    fn from(e: SweepError) -> Self {
+        Error::Sweep(e)
+    }
+}
@@ -1,5 +1,5 @@
 use {
-    crate::Particle,
+    crate::{Particle, G},
     cgmath::{
         prelude::*,
         {Point3, Vector3},
@@ -9,6 +9,7 @@ use {
     std::f32::consts::PI,
 };
 
+#[allow(clippy::too_many_arguments)]
 pub fn create(
     angle: f32,
     normal: Vector3<f32>,
@@ -28,19 +29,18 @@ pub fn create(
     let movement: Vector3<f32> = particle_vectors.cross(normal).normalize();
     // pos = center + offset * radius
     let pos: Point3<f32> = center_pos + particle_vectors * radius;
-    let gravity: f32 = 6.6e-31;
     // gravitational acceleration formula
-    let speed: f32 = (gravity * center_mass * radius as f32
-        / ((radius * radius) as f32 + calibrate))
-        .sqrt() as f32;
+    let speed: f32 = (G * center_mass * radius / ((radius * radius) + calibrate)).sqrt();
     // V' = V+g, g = gravitational acceleration * vector of movement
     let vel: Vector3<f32> = center_vel + movement * speed;
     let mass: f32 = 1e8;
     particles.push(Particle::new(pos.into(), vel.into(), mass, calibrate));
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn formation(
     particles: &mut Vec<Particle>,
+    rng: &mut impl Rng,
     amount: u32,
     calibrate: f32,
     center_pos: Point3<f32>,
@@ -49,8 +49,8 @@ pub fn formation(
     normal: Vector3<f32>,
 ) {
     for _ in 0..amount / 10 {
-        let radius: f32 = 5e-11 + thread_rng().gen_range(0.0..1e-13);
-        let angle: f32 = thread_rng().gen::<f32>() * 2.0 * PI;
+        let radius: f32 = 5e-11 + rng.gen_range(0.0..1e-13);
+        let angle: f32 = rng.gen::<f32>() * 2.0 * PI;
         create(
             angle,
             normal.normalize(),
@@ -64,8 +64,8 @@ pub fn formation(
     }
     // makes arms look more realistic
     for _ in 0..amount / 10 {
-        let radius: f32 = 5e-11 + thread_rng().gen_range(0.0..8e-10);
-        let angle: f32 = thread_rng().gen::<f32>() * 2.0 * PI;
+        let radius: f32 = 5e-11 + rng.gen_range(0.0..8e-10);
+        let angle: f32 = rng.gen::<f32>() * 2.0 * PI;
         create(
             angle,
             normal.normalize(),
@@ -80,14 +80,14 @@ pub fn formation(
     // based on number of stars in the arms vs center of Milky Way (80%)
     for _ in 0..amount / 5 * 4 {
         let arms: i32 = 2;
-        let radius: f32 = 7e-11 + thread_rng().gen_range(0.0..1e-9);
+        let radius: f32 = 7e-11 + rng.gen_range(0.0..1e-9);
         // θ = (2π / N) * A + f(r), N=total arms, A=arm number`
         // f(r) is a function that includes variation in the number
-        let arm: i32 = thread_rng().gen_range(0..arms);
+        let arm: i32 = rng.gen_range(0..arms);
         let angle: f32 = (arm as f32 / (arms as f32) * 2.0 * PI) - (radius * 7e9)
             + (Normal::new(0.0, PI / 8.0)
                 .unwrap()
-                .sample(&mut thread_rng()));
+                .sample(rng));
         create(
             angle,
             normal.normalize(),
@@ -1,31 +1,103 @@
 pub mod state;
 use state::State;
 
-use crate::{GpuInfo, Particle};
-use wgpu::util::DeviceExt;
+use {
+    crate::{
+        diagnostics::Diagnostics, error::Error, options::Options, snapshot, GpuInfo, Particle,
+    },
+    serde::Serialize,
+    std::{
+        fs,
+        io::{self, Write},
+        path::PathBuf,
+        time::Instant,
+    },
+};
 
-pub async fn run(mut gpu_info: GpuInfo, particles: Vec<Particle>, indexes: Vec<usize>) {
+// how long a headless run lasts and where its output goes
+#[derive(Clone, Debug)]
+pub struct Output {
+    pub steps: u64,
+    // 0 only writes the first and last step
+    pub snapshot_every: u64,
+    pub dir: Option<PathBuf>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Summary {
+    pub initial: Diagnostics,
+    pub last: Diagnostics,
+    pub energy_drift: f64,
+    pub seconds: f64,
+}
+
+// writes a snapshot and appends the tracked particles to tracks.csv
+fn record(
+    output: &Output,
+    tracks: &mut Option<io::BufWriter<fs::File>>,
+    indexes: &[usize],
+    step: u64,
+    time: f64,
+    particles: &[Particle],
+) -> io::Result<()> {
+    if let (Some(dir), Some(tracks)) = (&output.dir, tracks) {
+        snapshot::write(&snapshot::path(dir, step), step, time, particles)?;
+        for &i in indexes.iter().filter(|&&i| i < particles.len()) {
+            let p: &Particle = &particles[i];
+            writeln!(
+                tracks,
+                "{},{},{},{},{},{},{},{},{},{}",
+                step,
+                time,
+                p.id,
+                p.component,
+                p.pos[0],
+                p.pos[1],
+                p.pos[2],
+                p.vel[0],
+                p.vel[1],
+                p.vel[2]
+            )?;
+        }
+        tracks.flush()?;
+    }
+    Ok(())
+}
+
+pub async fn run(
+    mut gpu_info: GpuInfo,
+    particles: Vec<Particle>,
+    indexes: Vec<usize>,
+    output: &Output,
+    options: &Options,
+) -> Result<Summary, Error> {
     println!("{:?}", indexes);
-    let state: State = State::new(gpu_info, particles).await;
-    let n: usize = state.particles.len();
-    let p_size: u64 = (n * std::mem::size_of::<Particle>()) as u64;
-    let workgroups: u32 = ((n + 255 as usize) / 256 as usize) as u32;
+    let mut state: State = State::new(gpu_info, particles, options).await?;
     gpu_info.matrix = [[0.0; 4]; 4];
-    {
-        let mut encoder: wgpu::CommandEncoder =
-            state
-                .display
-                .device
-                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                    label: Some("Command Encoder"),
-                });
 
-        encoder.copy_buffer_to_buffer(&state.cur_init, 0, &state.cur, 0, p_size);
-
-        state.display.queue.submit([encoder.finish()]);
+    // output only fails to write with a directory
+    let failed = |e: io::Error| Error::Io(output.dir.clone().unwrap_or_default(), e);
+    let mut tracks: Option<io::BufWriter<fs::File>> = None;
+    if let Some(dir) = &output.dir {
+        fs::create_dir_all(dir).map_err(failed)?;
+        let mut file: io::BufWriter<fs::File> =
+            io::BufWriter::new(fs::File::create(dir.join("tracks.csv")).map_err(failed)?);
+        writeln!(file, "step,time,id,component,x,y,z,vx,vy,vz").map_err(failed)?;
+        tracks = Some(file);
     }
+    let initial: Diagnostics = Diagnostics::new(&state.particles, 0, 0.0);
+    record(output, &mut tracks, &indexes, 0, 0.0, &state.particles).map_err(failed)?;
 
-    loop {
+    let start: Instant = Instant::now();
+    let mut step: u64 = 0;
+    let mut particles: Vec<Particle> = state.particles.clone();
+    while step < output.steps {
+        // submit in batches that end on snapshot steps
+        let until_snapshot: u64 = match output.snapshot_every {
+            0 => u64::MAX,
+            every => every - step % every,
+        };
+        let batch: u64 = (output.steps - step).min(until_snapshot).min(64);
         let mut encoder: wgpu::CommandEncoder =
             state
                 .display
@@ -33,33 +105,43 @@ pub async fn run(mut gpu_info: GpuInfo, particles: Vec<Particle>, indexes: Vec<u
                 .create_command_encoder(&wgpu::CommandEncoderDescriptor {
                     label: Some("Command Encoder"),
                 });
-        let new_gpu_info: wgpu::Buffer =
-            state
-                .display
-                .device
-                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                    label: Some("GpuInfo Buffer"),
-                    contents: bytemuck::cast_slice(&[gpu_info]),
-                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_SRC,
-                });
+        state
+            .display
+            .queue
+            .write_buffer(&state.gpu_buffer, 0, bytemuck::cast_slice(&[gpu_info]));
 
-        encoder.copy_buffer_to_buffer(
-            &new_gpu_info,
-            0,
-            &state.gpu_buffer,
-            0,
-            std::mem::size_of::<GpuInfo>() as u64,
-        );
+        state
+            .kernel
+            .step(&mut encoder, &mut state.buffers, batch as u32);
+        state.display.queue.submit([encoder.finish()]);
+        state.display.failures.check()?;
+        step += batch;
 
-        for _ in 0..3 {
-            encoder.copy_buffer_to_buffer(&state.cur, 0, &state.prev, 0, p_size);
-            let mut cpass: wgpu::ComputePass<'_> =
-                encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
-                    label: Some("Compute Pass"),
-                });
-            cpass.set_pipeline(&state.comp_pipeline);
-            cpass.set_bind_group(0, &state.bind_group, &[]);
-            cpass.dispatch_workgroups(workgroups + 1, 1, 1);
+        if step == output.steps || batch == until_snapshot {
+            particles = state.read_particles().await?;
+            let time: f64 = step as f64 * gpu_info.motion as f64;
+            record(output, &mut tracks, &indexes, step, time, &particles).map_err(failed)?;
         }
     }
+
+    let seconds: f64 = start.elapsed().as_secs_f64();
+    let last: Diagnostics = Diagnostics::new(
+        &particles,
+        step,
+        step as f64 * gpu_info.motion as f64,
+    );
+    let summary: Summary = Summary {
+        energy_drift: last.drift(&initial),
+        initial,
+        last,
+        seconds,
+    };
+    println!(
+        "{} steps in {:.2}s ({:.1} steps/s), energy drift {:e}",
+        step,
+        seconds,
+        step as f64 / seconds,
+        summary.energy_drift
+    );
+    Ok(summary)
 }
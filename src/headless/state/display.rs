@@ -1,46 +1,24 @@
-use wgpu::Error;
+use crate::{
+    adapter::{self, Failures},
+    error::Error,
+    options::Options,
+};
 
 pub struct Display {
     pub device: wgpu::Device,
     pub queue: wgpu::Queue,
-    pub adapter: wgpu::Adapter,
+    pub failures: Failures,
 }
 
 impl Display {
-    pub async fn new() -> Result<Self, Error> {
-        let instance: wgpu::Instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
-            backends: wgpu::Backends::all(),
-            dx12_shader_compiler: Default::default(),
-        });
-        let adapter: wgpu::Adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::default(),
-                compatible_surface: None,
-                force_fallback_adapter: false,
-            })
-            .await
-            .unwrap();
-        let (device, queue) = adapter
-            .request_device(
-                &wgpu::DeviceDescriptor {
-                    label: None,
-                    features: wgpu::Features::SPIRV_SHADER_PASSTHROUGH
-                        | wgpu::Features::VERTEX_WRITABLE_STORAGE
-                        | wgpu::Features::MAPPABLE_PRIMARY_BUFFERS,
-                    limits: if cfg!(target_arch = "wasm32") {
-                        wgpu::Limits::downlevel_webgl2_defaults()
-                    } else {
-                        wgpu::Limits::default()
-                    },
-                },
-                None,
-            )
-            .await
-            .unwrap();
+    pub async fn new(options: &Options) -> Result<Self, Error> {
+        let instance: wgpu::Instance = adapter::instance(options);
+        let adapter: wgpu::Adapter = adapter::select(&instance, options, None)?;
+        let (device, queue, failures) = adapter::device(&adapter).await?;
         Ok(Self {
             device,
             queue,
-            adapter,
+            failures,
         })
     }
 }
@@ -2,26 +2,50 @@
 
 use rand::Rng;
 
+mod adapter;
+mod buffers;
+mod compute;
+mod diagnostics;
+mod error;
 mod gen;
 mod render;
 mod headless;
+mod options;
+mod scenario;
+mod snapshot;
+mod sweep;
 
 use {
-    cgmath::{Matrix4, Vector3, Point3, PerspectiveFov, Rad},
+    cgmath::{Matrix4, Vector3, Point3},
+    error::Error,
+    headless::Output,
+    options::{Mode, Options},
+    render::{
+        camera::{CameraPath, Lens},
+        offscreen::Frames,
+        replay::Replay,
+    },
+    scenario::Setup,
+    snapshot::Snapshot,
     serde::{Deserialize, Serialize},
-    std::f32::consts::PI,
+    std::{collections::BTreeMap, f32::consts::PI},
     rand::SeedableRng,
 };
 
 const CALIBRATE: f32 = 1e-1;
+const G: f32 = 6.6e-31;
+// default vertical field of view of the camera
+const FOVY: f32 = PI / 2.0;
 
 #[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable, Serialize, Deserialize)]
 #[repr(C)]
 pub struct Particle {
     pos: [f32; 3],
-    _pad1: f32,
+    // index of the galaxy the particle was generated from
+    component: u32,
     vel: [f32; 3],
-    _pad2: f32,
+    // stable across steps, snapshots and restarts
+    id: u32,
     mass: f32,
     calibrate: f32,
     _pad3: [f32; 2],
@@ -52,6 +76,17 @@ pub struct GpuInfo {
     _pad1: [f32; 2],
 }
 
+impl GpuInfo {
+    fn new(particles: u32) -> Self {
+        Self {
+            matrix: Matrix4::from_translation(Vector3::new(0.0, 0.0, 0.0)).into(),
+            particles,
+            motion: 2.0,
+            _pad1: [0.0; 2],
+        }
+    }
+}
+
 impl Particle {
     fn new(pos: [f32; 3], vel: [f32; 3], mass: f32, calibrate: f32) -> Self {
         Self {
@@ -59,44 +94,67 @@ impl Particle {
             vel,
             mass,
             calibrate,
-            _pad1: 0.0,
-            _pad2: 0.0,
+            component: 0,
+            id: 0,
             _pad3: [0.0; 2],
         }
     }
 }
 
-fn build_matrix(pos: Point3<f32>, dir: Vector3<f32>, aspect: f32) -> Matrix4<f32> {
-    Matrix4::from(PerspectiveFov {
-        fovy: Rad(PI / 2.0),
-        aspect,
-        near: 1E-10,
-        far: 1E7,
-    }) * Matrix4::look_to_rh(pos, dir, Vector3::new(0.0, 1.0, 0.0))
+fn build_matrix(pos: Point3<f32>, dir: Vector3<f32>, aspect: f32, lens: Lens) -> Matrix4<f32> {
+    build_matrix_up(pos, dir, Vector3::new(0.0, 1.0, 0.0), aspect, lens)
 }
 
-pub fn init_galaxy(calibrate: f32, galaxies: Vec<Galaxy>) -> Vec<Particle> {
+// build_matrix with another up direction, for views looking along y; wgpu clips
+// depth to 0..1, unlike the opengl matrices of cgmath, so the projection is built here
+fn build_matrix_up(
+    pos: Point3<f32>,
+    dir: Vector3<f32>,
+    up: Vector3<f32>,
+    aspect: f32,
+    lens: Lens,
+) -> Matrix4<f32> {
+    let (near, far) = lens.clip_planes();
+    #[rustfmt::skip]
+    let projection: Matrix4<f32> = if lens.orthographic {
+        let half_height: f32 = lens.half_height();
+        Matrix4::new(
+            1.0 / (half_height * aspect), 0.0, 0.0, 0.0,
+            0.0, 1.0 / half_height, 0.0, 0.0,
+            0.0, 0.0, 1.0 / (near - far), 0.0,
+            0.0, 0.0, near / (near - far), 1.0,
+        )
+    } else {
+        let f: f32 = 1.0 / (lens.fovy / 2.0).tan();
+        Matrix4::new(
+            f / aspect, 0.0, 0.0, 0.0,
+            0.0, f, 0.0, 0.0,
+            0.0, 0.0, far / (near - far), -1.0,
+            0.0, 0.0, near * far / (near - far), 0.0,
+        )
+    };
+    projection * Matrix4::look_to_rh(pos, dir, up)
+}
+
+pub fn init_galaxy(calibrate: f32, galaxies: Vec<Galaxy>, seed: u64) -> Vec<Particle> {
+    let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
     let mut particles: Vec<Particle> = Vec::new();
     for c in &galaxies {
         particles.push(match c {
             Galaxy::Particle { pos, vel, mass } => {
-                Particle::new((*pos).into(), (*vel).into(), *mass, calibrate)
+                Particle::new(*pos, *vel, *mass, calibrate)
             }
             Galaxy::Init {
                 center_pos,
                 center_vel,
                 center_mass,
                 ..
-            } => Particle::new(
-                (*center_pos).into(),
-                (*center_vel).into(),
-                *center_mass,
-                calibrate,
-            ),
+            } => Particle::new(*center_pos, *center_vel, *center_mass, calibrate),
         })
     }
 
-    for i in &galaxies {
+    for (component, i) in galaxies.iter().enumerate() {
+        particles[component].component = component as u32;
         if let Galaxy::Init {
             center_pos,
             center_vel,
@@ -105,8 +163,10 @@ pub fn init_galaxy(calibrate: f32, galaxies: Vec<Galaxy>) -> Vec<Particle> {
             normal,
         } = i
         {
+            let start: usize = particles.len();
             gen::formation(
                 &mut particles,
+                &mut rng,
                 *amount,
                 CALIBRATE,
                 (*center_pos).into(),
@@ -114,13 +174,19 @@ pub fn init_galaxy(calibrate: f32, galaxies: Vec<Galaxy>) -> Vec<Particle> {
                 *center_mass,
                 (*normal).into(),
             );
+            for p in &mut particles[start..] {
+                p.component = component as u32;
+            }
         }
     }
+    for (id, p) in particles.iter_mut().enumerate() {
+        p.id = id as u32;
+    }
     particles
 }
 
-fn main() {
-    let galaxies: Vec<Galaxy> = vec![
+fn default_galaxies() -> Vec<Galaxy> {
+    vec![
         Galaxy::Init {
             center_pos: [-2e-9, -2e-9, 0.0],
             center_vel: [1e-14, 0.0, 0.0],
@@ -135,31 +201,104 @@ fn main() {
             amount: 10000,
             normal: [1.0, 1.0, 0.0],
         },
-    ];
-
-    let particles: Vec<Particle> = init_galaxy(CALIBRATE, galaxies);
-    let gpu_info: GpuInfo = GpuInfo {
-        matrix: Matrix4::from_translation(Vector3::new(0.0, 0.0, 0.0)).into(),
-        particles: particles.len() as u32,
-        motion: 2.0,
-        _pad1: [0.0; 2],
-    };
+    ]
+}
 
-    let render: bool = false;
-    if render {
-        pollster::block_on(render::run(gpu_info, particles));
-    } else {
-        let mut rng = rand::rngs::StdRng::seed_from_u64(0);
-        let mut indexes: Vec<usize> = Vec::new();
-        indexes.push(0);
-        indexes.push(1);
+// the first particle of each component, its center, plus a few random stars from each
+pub fn tracked_indexes(particles: &[Particle], seed: u64) -> Vec<usize> {
+    let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+    let mut members: BTreeMap<u32, Vec<usize>> = BTreeMap::new();
+    for (i, p) in particles.iter().enumerate() {
+        members.entry(p.component).or_default().push(i);
+    }
+    let mut indexes: Vec<usize> = members.values().map(|m| m[0]).collect();
+    for m in members.values().filter(|m| m.len() > 2) {
         for _ in 0..4 {
-            let index: usize = rng.gen_range(2..particles.len()/2);
-            let index2: usize = rng.gen_range(2..particles.len()/2) + particles.len()/2;
-            indexes.push(index);
-            indexes.push(index2);
+            indexes.push(m[rng.gen_range(1..m.len())]);
+        }
+    }
+    indexes
+}
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("{}", e);
+        std::process::exit(e.exit_code());
+    }
+}
+
+fn run() -> Result<(), Error> {
+    let options: Options = Options::parse(std::env::args().skip(1)).map_err(Error::Usage)?;
+    if options.mode == Mode::Adapters {
+        for adapter in adapter::list(&options) {
+            println!("{}", adapter);
         }
+        return Ok(());
+    }
+    if let Mode::Sweep(path) = &options.mode {
+        return Ok(pollster::block_on(sweep::run(path, &options))?);
+    }
 
-        pollster::block_on(headless::run(gpu_info, particles, indexes));        
+    let setup: Setup = match &options.scenario {
+        Some(path) => scenario::load(path, &[])?,
+        None => Setup {
+            seed: 0,
+            galaxies: default_galaxies(),
+        },
+    };
+
+    let mut replay: Option<Replay> = match &options.mode {
+        Mode::Replay(dir) => Some(Replay::load(dir).map_err(|e| Error::Io(dir.clone(), e))?),
+        _ => None,
+    };
+    let particles: Vec<Particle> = match (&options.mode, &options.snapshot, replay.as_mut()) {
+        // the first snapshot of the replay
+        (Mode::Replay(dir), _, Some(replay)) => {
+            let first: Snapshot = replay.first().map_err(|e| Error::Io(dir.clone(), e))?;
+            first.particles
+        }
+        (_, Some(path), _) => {
+            let snapshot: Snapshot =
+                snapshot::read(path).map_err(|e| Error::Io(path.clone(), e))?;
+            println!("starting from step {} (t = {})", snapshot.step, snapshot.time);
+            snapshot.particles
+        }
+        _ => init_galaxy(CALIBRATE, setup.galaxies, setup.seed),
+    };
+    let gpu_info: GpuInfo = GpuInfo::new(particles.len() as u32);
+
+    let mut indexes: Vec<usize> = tracked_indexes(&particles, setup.seed);
+    for &id in &options.track {
+        match particles.iter().position(|p| p.id == id) {
+            Some(i) if !indexes.contains(&i) => indexes.push(i),
+            Some(_) => {}
+            None => eprintln!("no particle with id {} to track", id),
+        }
     }
-}
\ No newline at end of file
+    if options.mode == Mode::Render || replay.is_some() {
+        pollster::block_on(render::run(gpu_info, particles, indexes, &options, replay))
+    } else if let Mode::Frames(dir) = &options.mode {
+        let camera_path: Option<CameraPath> = match &options.camera_path {
+            Some(path) => Some(CameraPath::load(path).map_err(|e| Error::Io(path.clone(), e))?),
+            None => None,
+        };
+        let frames: Frames = Frames {
+            dir: dir.clone(),
+            steps: options.steps,
+            every: options.frame_every,
+            width: options.size.0,
+            height: options.size.1,
+            orbit: options.orbit,
+            camera_path,
+        };
+        pollster::block_on(render::offscreen::run(gpu_info, particles, indexes, &frames, &options))
+    } else {
+        let output: Output = Output {
+            steps: options.steps,
+            snapshot_every: options.snapshot_every,
+            dir: options.out.clone(),
+        };
+        pollster::block_on(headless::run(gpu_info, particles, indexes, &output, &options))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,188 @@
+use {
+    crate::{
+        adapter::Backend,
+        compute::DEFAULT_WORKGROUP_SIZE,
+        render::{state::display::PresentMode, trail},
+    },
+    std::path::PathBuf,
+};
+
+pub const USAGE: &str = "usage: nbodysim [--render | --sweep SWEEP | --frames DIR | --replay DIR | --adapters] [--steps N] \
+[--snapshot-every N] [--out DIR] [--snapshot FILE] [--workgroup-size N] [--frame-every N] \
+[--size WxH] [--orbit DEGREES] [--trails off|tracked|all] [--camera-path FILE] [--keys FILE] [--present-mode vsync|mailbox|immediate] [--rate TIME] \
+[--backend all|vulkan|metal|dx12|gl] [--adapter INDEX|NAME] [--fallback] [--hud] [--projection] [--orthographic] [--track ID,...] [SCENARIO]";
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Mode {
+    Render,
+    Headless,
+    Sweep(PathBuf),
+    // render png frames into the directory without a window
+    Frames(PathBuf),
+    // play back the snapshots in the directory in the window
+    Replay(PathBuf),
+    // list the gpu adapters and exit
+    Adapters,
+}
+
+#[derive(Clone, Debug)]
+pub struct Options {
+    pub mode: Mode,
+    pub scenario: Option<PathBuf>,
+    // start from a snapshot instead of the scenario
+    pub snapshot: Option<PathBuf>,
+    // headless only
+    pub steps: u64,
+    pub snapshot_every: u64,
+    pub out: Option<PathBuf>,
+    // invocations per compute workgroup, also the force kernel's tile size
+    pub workgroup_size: u32,
+    // offscreen frames only
+    pub frame_every: u64,
+    pub size: (u32, u32),
+    pub orbit: f32,
+    pub trails: trail::Mode,
+    // keyframes K records to in the window and that drive both cameras when present
+    pub camera_path: Option<PathBuf>,
+    // key bindings for the window, keys.ron when it exists
+    pub keys: Option<PathBuf>,
+    pub present_mode: PresentMode,
+    // simulated time the window advances per wall clock second, whatever the frame rate
+    pub rate: f32,
+    // where the gpu adapter is looked for, the index or part of the name of one of
+    // those --adapters lists, and whether only software adapters are used
+    pub backend: Backend,
+    pub adapter: Option<String>,
+    pub fallback: bool,
+    // draw the hud onto offscreen frames, the window toggles it with H
+    pub hud: bool,
+    // start with the projected density instead of the particles
+    pub projection: bool,
+    // start with an orthographic projection, the window toggles it with O
+    pub orthographic: bool,
+    // particle ids tracked besides the ones picked from each component
+    pub track: Vec<u32>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            mode: Mode::Headless,
+            scenario: None,
+            snapshot: None,
+            steps: 1000,
+            snapshot_every: 0,
+            out: None,
+            workgroup_size: DEFAULT_WORKGROUP_SIZE,
+            frame_every: 10,
+            size: (1280, 720),
+            orbit: 0.0,
+            trails: trail::Mode::Off,
+            camera_path: None,
+            keys: None,
+            present_mode: PresentMode::Vsync,
+            // 180 steps a second of the starting dt, 3 a frame at 60 hz
+            rate: 360.0,
+            backend: Backend::All,
+            adapter: None,
+            fallback: false,
+            hud: false,
+            projection: false,
+            orthographic: false,
+            track: Vec::new(),
+        }
+    }
+}
+
+fn value<T: std::str::FromStr>(
+    flag: &str,
+    args: &mut impl Iterator<Item = String>,
+) -> Result<T, String> {
+    let arg: String = args
+        .next()
+        .ok_or_else(|| format!("{} needs a value", flag))?;
+    arg.parse()
+        .map_err(|_| format!("invalid value for {}: {}", flag, arg))
+}
+
+// WIDTHxHEIGHT, both non-zero
+fn size(flag: &str, args: &mut impl Iterator<Item = String>) -> Result<(u32, u32), String> {
+    let arg: String = value(flag, args)?;
+    arg.split_once('x')
+        .and_then(|(w, h)| Some((w.parse().ok()?, h.parse().ok()?)))
+        .filter(|&(w, h)| w > 0 && h > 0)
+        .ok_or_else(|| format!("invalid value for {}: {}", flag, arg))
+}
+
+// finite and above zero
+fn positive(flag: &str, args: &mut impl Iterator<Item = String>) -> Result<f32, String> {
+    let arg: String = value(flag, args)?;
+    arg.parse()
+        .ok()
+        .filter(|&x: &f32| x > 0.0 && x.is_finite())
+        .ok_or_else(|| format!("invalid value for {}: {}", flag, arg))
+}
+
+// comma separated ids
+fn ids(flag: &str, args: &mut impl Iterator<Item = String>) -> Result<Vec<u32>, String> {
+    let arg: String = value(flag, args)?;
+    arg.split(',')
+        .map(|id| id.trim().parse())
+        .collect::<Result<_, _>>()
+        .map_err(|_| format!("invalid value for {}: {}", flag, arg))
+}
+
+impl Options {
+    pub fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut options: Options = Options::default();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--render" => options.mode = Mode::Render,
+                "--sweep" => options.mode = Mode::Sweep(value(&arg, &mut args)?),
+                "--steps" => options.steps = value(&arg, &mut args)?,
+                "--snapshot-every" => options.snapshot_every = value(&arg, &mut args)?,
+                "--out" => options.out = Some(value(&arg, &mut args)?),
+                "--snapshot" => options.snapshot = Some(value(&arg, &mut args)?),
+                "--workgroup-size" => options.workgroup_size = value(&arg, &mut args)?,
+                "--frames" => options.mode = Mode::Frames(value(&arg, &mut args)?),
+                "--replay" => options.mode = Mode::Replay(value(&arg, &mut args)?),
+                "--frame-every" => options.frame_every = value(&arg, &mut args)?,
+                "--size" => options.size = size(&arg, &mut args)?,
+                "--orbit" => options.orbit = value(&arg, &mut args)?,
+                "--trails" => options.trails = value(&arg, &mut args)?,
+                "--camera-path" => options.camera_path = Some(value(&arg, &mut args)?),
+                "--keys" => options.keys = Some(value(&arg, &mut args)?),
+                "--present-mode" => options.present_mode = value(&arg, &mut args)?,
+                "--rate" => options.rate = positive(&arg, &mut args)?,
+                "--adapters" => options.mode = Mode::Adapters,
+                "--backend" => options.backend = value(&arg, &mut args)?,
+                "--adapter" => options.adapter = Some(value(&arg, &mut args)?),
+                "--fallback" => options.fallback = true,
+                "--hud" => options.hud = true,
+                "--projection" => options.projection = true,
+                "--orthographic" => options.orthographic = true,
+                "--track" => options.track = ids(&arg, &mut args)?,
+                flag if flag.starts_with("--") => return Err(format!("unknown option {}", flag)),
+                _ => options.scenario = Some(arg.into()),
+            }
+        }
+        Ok(options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Options, String> {
+        Options::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn size_needs_both_sides() {
+        assert_eq!(parse(&["--size", "640x480"]).map(|o| o.size), Ok((640, 480)));
+        for size in ["0x480", "640x0", "640", "x480", "-1x480"] {
+            assert!(parse(&["--size", size]).is_err(), "{} was accepted", size);
+        }
+    }
+}
@@ -1,18 +1,129 @@
-use wgpu::{util::DeviceExt, SurfaceTexture};
+use wgpu::SurfaceTexture;
 use {
-    crate::{GpuInfo, Particle, build_matrix},
-    cgmath::{prelude::*, Point3, Quaternion, Rad, Vector3},
-    std::{collections::HashSet, time::Instant},
-    winit::{event, event_loop::ControlFlow},
+    crate::{
+        buffers::{ParticleBuffers, Readback},
+        diagnostics::DriftMeter, error::Error, options::Options, GpuInfo, Particle,
+        build_matrix, build_matrix_up, FOVY,
+    },
+    bindings::{Action, Bindings},
+    camera::{CameraPath, Keyframe, Lens, Mode as CameraMode, Rig, View},
+    cgmath::{prelude::*, Deg, Matrix4, Point3, Quaternion, Rad, Vector3},
+    hud::Stats,
+    layout::{Layout, Viewport},
+    replay::Replay,
+    scene::Scene,
+    std::{
+        collections::HashSet,
+        path::PathBuf,
+        time::{Duration, Instant},
+    },
+    winit::{
+        event,
+        event_loop::{ControlFlow, EventLoop},
+        platform::run_return::EventLoopExtRunReturn,
+        window::{CursorGrabMode, Window},
+    },
 };
+pub mod bindings;
+pub mod camera;
+pub mod color;
+pub mod hud;
+pub mod layout;
+pub mod offscreen;
+pub mod overlay;
+pub mod pick;
+pub mod post;
+pub mod projection;
+pub mod replay;
+pub mod scene;
 pub mod state;
-use state::State;
+pub mod trail;
+use state::{display::Display, State};
 
-pub async fn run(mut gpu_info: GpuInfo, particles: Vec<Particle>) {
-    let mut state: State = State::new(gpu_info, particles).await;
-    let n: usize = state.particles.len();
-    let p_size: u64 = (n * std::mem::size_of::<Particle>()) as u64;
-    let workgroups: u32 = ((n + 255 as usize) / 256 as usize) as u32;
+// where the camera starts, looking at the origin
+pub const CAMERA_POS: [f32; 3] = [0.0, 0.0, 4e-9];
+// the energy is a pairwise sum on the cpu, so the hud only shows the drift for runs
+// up to this size, measured off the render thread at most once a second
+const DRIFT_LIMIT: usize = 32768;
+// the most steps a frame takes, a slower frame drops the simulated time past them
+// rather than owing more steps to the next one
+const MAX_STEPS: u32 = 64;
+// the most steps Right splits a step of dt into
+const MAX_SUBSTEPS: u32 = 16;
+
+// where the rig's target is now, Orbit keeps the point it started with
+fn rig_target(
+    rig: &Rig,
+    buffers: &ParticleBuffers,
+    display: &Display,
+    particles: &[Particle],
+    tracked: &[usize],
+    time: f32,
+) -> Result<Point3<f32>, Error> {
+    Ok(match rig.mode {
+        CameraMode::Follow if !tracked.is_empty() => {
+            let index: usize = tracked[rig.follow % tracked.len()];
+            let particle: Vec<Particle> = pollster::block_on(buffers.read_range(
+                &display.device,
+                &display.queue,
+                index..index + 1,
+            ))?;
+            particle[0].pos.into()
+        }
+        CameraMode::CenterOfMass => {
+            let (pos, vel) = camera::center_of_mass(particles);
+            pos + vel * time
+        }
+        _ => rig.target,
+    })
+}
+
+// grabs and hides the cursor for mouse look, or gives it back
+fn capture(window: &Window, captured: bool) {
+    let grab: Result<(), winit::error::ExternalError> = if captured {
+        window
+            .set_cursor_grab(CursorGrabMode::Confined)
+            .or_else(|_| window.set_cursor_grab(CursorGrabMode::Locked))
+    } else {
+        window.set_cursor_grab(CursorGrabMode::None)
+    };
+    if let Err(e) = grab {
+        eprintln!("failed to grab the cursor: {}", e);
+    }
+    window.set_cursor_visible(!captured);
+}
+
+// sizes what is drawn per viewport to the layout's tiles and the hud to the window
+fn resize_scene(display: &Display, scene: &mut Scene, layout: &Layout) {
+    let (width, height) = (display.config.width, display.config.height);
+    let [tile_width, tile_height] = layout.tile(width, height);
+    scene.post.resize(&display.device, tile_width, tile_height);
+    scene.post.update(&display.queue);
+    scene.hud.resize(width, height);
+    scene.overlay.resize(tile_width, tile_height);
+    scene.projection.resize(
+        &display.device,
+        &scene.coloring.colormaps,
+        tile_width,
+        tile_height,
+    );
+}
+
+pub async fn run(
+    mut gpu_info: GpuInfo,
+    particles: Vec<Particle>,
+    mut indexes: Vec<usize>,
+    options: &Options,
+    // plays these snapshots back instead of simulating
+    mut replay: Option<Replay>,
+) -> Result<(), Error> {
+    let centers: Vec<u32> = indexes.iter().map(|&i| i as u32).collect();
+    let mut state: State = State::new(gpu_info, particles, centers, options).await?;
+    state
+        .scene
+        .trails
+        .set_mode(&state.display.device, options.trails, state.buffers.count);
+    state.scene.projection.enabled = options.projection;
 
     let mut cam: Vector3<f32> = Vector3::new(
         -state.display.camera_pos[0],
@@ -20,37 +131,87 @@ pub async fn run(mut gpu_info: GpuInfo, particles: Vec<Particle>) {
         -state.display.camera_pos[2],
     );
     cam = cam.normalize();
+    let camera_distance: f32 = Vector3::from(CAMERA_POS).magnitude();
+    let mut lens: Lens = Lens::new(FOVY, camera::extent(&state.particles, camera_distance));
+    lens.distance = Vector3::from(state.display.camera_pos).magnitude();
+    lens.orthographic = options.orthographic;
     gpu_info.matrix = build_matrix(
         state.display.camera_pos.into(),
         cam,
         state.display.size.width as f32 / state.display.size.height as f32,
+        lens,
     )
     .into();
+    // K appends the current camera to this file, Y plays it back
+    let path_file: PathBuf = options
+        .camera_path
+        .clone()
+        .unwrap_or_else(|| "camera_path.ron".into());
+    let mut camera_path: CameraPath =
+        CameraPath::load_or_default(&path_file).map_err(|e| Error::Io(path_file.clone(), e))?;
+    let keys_file: PathBuf = options.keys.clone().unwrap_or_else(|| "keys.ron".into());
+    let bindings: Bindings =
+        Bindings::load_or_default(&keys_file).map_err(|e| Error::Io(keys_file.clone(), e))?;
+    // whether mouse motion turns the camera, right click toggles it
+    let mut captured: bool = false;
+    let mut playing: bool = options.camera_path.is_some() && !camera_path.keyframes.is_empty();
+    // simulated time, what camera keyframes are timed by
+    let mut time: f32 = 0.0;
+    let mut rig: Rig = Rig::new();
+    // the particle Follow is after, copied back across frames
+    let mut following: Readback = Readback::new(&state.display.device, 1);
+    // steps taken, less the ones taken back in reverse
+    let mut step: i64 = 0;
+    // the step size, negative in reverse, the steps each one is split into and the
+    // simulated time per wall clock second the steps are taken at
+    let mut motion: f32 = gpu_info.motion;
+    let mut substeps: u32 = 1;
+    let mut rate: f32 = options.rate;
+    // simulated time owed to steps, less than one step after a frame takes them
+    let mut owed: f32 = 0.0;
+    let mut paused: bool = false;
+    // steps N asked for while paused
+    let mut single: u32 = 0;
+    // smoothed wall clock seconds per frame
+    let mut frame_time: f32 = 0.0;
+    // the drift from the initial particles and the copies of the particles it is
+    // measured on
+    let mut drift: Option<(DriftMeter, Readback)> =
+        (state.particles.len() <= DRIFT_LIMIT).then(|| {
+            (
+                DriftMeter::new(state.particles.clone()),
+                Readback::new(&state.display.device, state.particles.len()),
+            )
+        });
+    let mut energy_drift: Option<f64> = None;
+    let mut measured: Instant = Instant::now();
+    // in physical pixels from the top left
+    let mut cursor: [f32; 2] = [0.0; 2];
+    // the clicked particle and what it orbits, and the hud's lines about them,
+    // refreshed once a second with the energy drift
+    let mut picked: Option<(usize, Option<usize>)> = None;
+    let mut inspection: Vec<String> = Vec::new();
+    // the viewports of the last frame with the matrices they were drawn with
+    let mut drawn: Vec<(Viewport, Matrix4<f32>)> = Vec::new();
+    // the overlay's center of mass marker and the fixed viewpoints move with it
+    let (com, com_vel) = camera::center_of_mass(&state.particles);
+    let mut layout: Layout = Layout::new();
+    // far enough for the fixed viewpoints to take in the initial particles
+    let fixed_distance: f32 = 1.2 * lens.extent / (FOVY / 2.0).tan();
     let vel: f32 = 1E-9;
     let mut keys: HashSet<event::VirtualKeyCode> = HashSet::new();
     let mut right: Vector3<f32> = cam.cross(Vector3::new(0.0, 1.0, 0.0)).normalize();
     let mut update: Instant = Instant::now();
-    {
-        let mut encoder: wgpu::CommandEncoder =
-            state
-                .display
-                .device
-                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                    label: Some("Command Encoder"),
-                });
-
-        encoder.copy_buffer_to_buffer(&state.cur_init, 0, &state.cur, 0, p_size);
-
-        state.display.queue.submit([encoder.finish()]);
-    }
-    let prev_motion: f32 = gpu_info.motion;
-    state.event_loop.run(move |event, _, control_flow| {
+    // what ended the loop, if it didn't end with the window
+    let mut failure: Option<Error> = None;
+    let mut event_loop: EventLoop<()> = state.event_loop;
+    event_loop.run_return(|event, _, control_flow| {
         *control_flow = ControlFlow::Poll;
         match event {
             event::Event::DeviceEvent {
                 event: event::DeviceEvent::MouseMotion { delta },
                 ..
-            } => {
+            } if captured => {
                 cam = Quaternion::from_angle_y(Rad(-delta.0 as f32 / 300.0)).rotate_vector(cam);
                 cam = Quaternion::from_axis_angle(right, Rad(delta.1 as f32 / 300.0))
                     .rotate_vector(cam);
@@ -70,15 +231,191 @@ pub async fn run(mut gpu_info: GpuInfo, particles: Vec<Particle>) {
                         },
                     ..
                 } => {
-                    match key {
-                        event::VirtualKeyCode::Escape => {
+                    let action: Option<Action> = bindings.action(key);
+                    match action {
+                        // the replay takes the time controls over
+                        Some(action)
+                            if replay.as_mut().is_some_and(|replay| replay.act(action)) =>
+                        {
+                            println!("{}", replay.as_ref().unwrap().describe());
+                            // a jump would streak across the screen
+                            state.scene.trails.reset();
+                        }
+                        Some(Action::Quit) if captured => {
+                            captured = false;
+                            capture(&state.display.window, captured);
+                        }
+                        Some(Action::Quit) => {
                             *control_flow = ControlFlow::Exit;
                         }
-                        event::VirtualKeyCode::P => {
-                            gpu_info.motion = 0.0;
+                        Some(Action::Pause) => paused = true,
+                        Some(Action::Resume) => paused = false,
+                        Some(Action::Step) if paused => single += 1,
+                        Some(
+                            Action::Faster
+                            | Action::Slower
+                            | Action::FewerSteps
+                            | Action::MoreSteps
+                            | Action::SlowerRate
+                            | Action::FasterRate
+                            | Action::Reverse,
+                        ) => {
+                            match action {
+                                Some(Action::Faster) => motion *= 2.0,
+                                Some(Action::Slower) => motion *= 0.5,
+                                Some(Action::FewerSteps) => substeps = (substeps - 1).max(1),
+                                Some(Action::MoreSteps) => {
+                                    substeps = (substeps + 1).min(MAX_SUBSTEPS)
+                                }
+                                Some(Action::SlowerRate) => rate *= 0.5,
+                                Some(Action::FasterRate) => rate *= 2.0,
+                                // the window's leapfrog retraces its steps with dt negated
+                                _ => motion = -motion,
+                            }
+                            println!(
+                                "dt {:e} in {} steps, {:e} simulated time per second{}",
+                                motion,
+                                substeps,
+                                rate,
+                                if motion < 0.0 { ", reversed" } else { "" }
+                            );
+                        }
+                        // back to the particles the run started from
+                        Some(Action::Restart) => {
+                            state.buffers.upload(
+                                &state.display.device,
+                                &state.display.queue,
+                                &state.gpu_buffer,
+                                &state.particles,
+                            );
+                            gpu_info.particles = state.particles.len() as u32;
+                            state.scene.trails.reset();
+                            time = 0.0;
+                            step = 0;
+                        }
+                        Some(Action::Keyframe) => {
+                            let pos: Vector3<f32> = state.display.camera_pos.into();
+                            // looks as far ahead as the camera is from the origin
+                            let look_at: Vector3<f32> = pos + cam * pos.magnitude();
+                            camera_path.insert(Keyframe {
+                                time,
+                                position: pos.into(),
+                                look_at: look_at.into(),
+                                fov: Deg::from(Rad(lens.fovy)).0,
+                            });
+                            match camera_path.save(&path_file) {
+                                Ok(()) => println!(
+                                    "keyframe {} at t = {} saved to {}",
+                                    camera_path.keyframes.len(),
+                                    time,
+                                    path_file.display()
+                                ),
+                                Err(e) => eprintln!("failed to save camera path: {}", e),
+                            }
+                        }
+                        Some(
+                            Action::FreeCamera
+                            | Action::OrbitCamera
+                            | Action::FollowCamera
+                            | Action::CenterOfMassCamera,
+                        ) => {
+                            rig.mode = match action {
+                                Some(Action::FreeCamera) => CameraMode::Free,
+                                Some(Action::OrbitCamera) => CameraMode::Orbit,
+                                Some(Action::FollowCamera) => CameraMode::Follow,
+                                _ => CameraMode::CenterOfMass,
+                            };
+                            let target: Point3<f32> = match rig_target(
+                                &rig,
+                                &state.buffers,
+                                &state.display,
+                                &state.particles,
+                                &indexes,
+                                time,
+                            ) {
+                                Ok(target) => target,
+                                Err(e) => {
+                                    failure = Some(e);
+                                    *control_flow = ControlFlow::Exit;
+                                    return;
+                                }
+                            };
+                            rig.set_mode(rig.mode, state.display.camera_pos.into(), cam, target);
+                            println!("{}", rig.mode.describe());
+                        }
+                        // next tracked particle to follow
+                        Some(Action::NextTarget) if !indexes.is_empty() => {
+                            rig.follow = (rig.follow + 1) % indexes.len();
+                            println!("following particle {}", indexes[rig.follow]);
+                        }
+                        // adds the picked particle to the tracked ones
+                        Some(Action::Track) => {
+                            if let Some((i, _)) = picked.filter(|(i, _)| !indexes.contains(i)) {
+                                indexes.push(i);
+                                state.scene.coloring.centers.push(i as u32);
+                                state.scene.trails.tracked.push(i as u32);
+                                if state.scene.trails.mode == trail::Mode::Tracked {
+                                    state.scene.trails.set_mode(
+                                        &state.display.device,
+                                        trail::Mode::Tracked,
+                                        state.buffers.count,
+                                    );
+                                }
+                            }
+                            let ids: Vec<String> = indexes
+                                .iter()
+                                .map(|&i| state.particles[i].id.to_string())
+                                .collect();
+                            println!("tracking --track {}", ids.join(","));
+                        }
+                        Some(Action::Orthographic) => {
+                            lens.orthographic = !lens.orthographic;
+                            println!("{}", lens.describe());
+                        }
+                        Some(Action::PlayPath) => {
+                            playing = !playing && !camera_path.keyframes.is_empty();
+                            println!("camera path {}", if playing { "playing" } else { "stopped" });
+                        }
+                        Some(action) => {
+                            if state.scene.coloring.act(action) {
+                                // fit to where the particles are now, not where they started
+                                if state.scene.coloring.stale {
+                                    match pollster::block_on(
+                                        state
+                                            .buffers
+                                            .read(&state.display.device, &state.display.queue),
+                                    ) {
+                                        Ok(particles) => state.scene.coloring.fit_range(&particles),
+                                        Err(e) => {
+                                            failure = Some(e);
+                                            *control_flow = ControlFlow::Exit;
+                                            return;
+                                        }
+                                    }
+                                }
+                                state.scene.coloring.update(&state.display.queue);
+                                println!("{}", state.scene.coloring.describe());
+                            } else if state.scene.post.act(action) {
+                                state.scene.post.update(&state.display.queue);
+                                println!("{}", state.scene.post.describe());
+                            } else if state.scene.trails.act(
+                                &state.display.device,
+                                action,
+                                state.buffers.count,
+                            ) {
+                                println!("{}", state.scene.trails.describe());
+                            } else if state.scene.projection.act(action) {
+                                println!("{}", state.scene.projection.describe());
+                            } else if state.scene.overlay.act(action) {
+                                println!("{}", state.scene.overlay.describe());
+                            } else if layout.act(action) {
+                                resize_scene(&state.display, &mut state.scene, &layout);
+                                println!("{}", layout.describe());
+                            } else if state.scene.hud.act(action) {
+                                println!("{}", state.scene.hud.describe());
+                            }
                         }
-                        event::VirtualKeyCode::R => gpu_info.motion = prev_motion,
-                        _ => {}
+                        None => {}
                     }
                     keys.insert(key);
                 }
@@ -94,31 +431,77 @@ pub async fn run(mut gpu_info: GpuInfo, particles: Vec<Particle>) {
                 } => {
                     keys.remove(&key);
                 }
-                event::WindowEvent::Resized(resized) => {
-                    state.display.size = resized;
-
-                    state.display.resize(resized.width, resized.height);
-
-                    let depth_texture: wgpu::Texture =
-                        state
-                            .display
-                            .device
-                            .create_texture(&wgpu::TextureDescriptor {
-                                label: Some("New Depth Texture"),
-                                size: wgpu::Extent3d {
-                                    width: state.display.config.width,
-                                    height: state.display.config.height,
-                                    depth_or_array_layers: 1,
-                                },
-                                view_formats: &[],
-                                mip_level_count: 1,
-                                sample_count: 1,
-                                dimension: wgpu::TextureDimension::D2,
-                                format: wgpu::TextureFormat::Depth32Float,
-                                usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-                            });
-                    state.depth_view =
-                        depth_texture.create_view(&wgpu::TextureViewDescriptor::default());
+                event::WindowEvent::MouseWheel { delta, .. } => {
+                    lens.zoom(match delta {
+                        event::MouseScrollDelta::LineDelta(_, lines) => lines,
+                        event::MouseScrollDelta::PixelDelta(pixels) => pixels.y as f32 / 40.0,
+                    });
+                }
+                event::WindowEvent::MouseInput {
+                    state: event::ElementState::Pressed,
+                    button: event::MouseButton::Right,
+                    ..
+                } => {
+                    captured = !captured;
+                    capture(&state.display.window, captured);
+                }
+                event::WindowEvent::Focused(false) if captured => {
+                    captured = false;
+                    capture(&state.display.window, captured);
+                }
+                event::WindowEvent::CursorMoved { position, .. } => {
+                    cursor = [position.x as f32, position.y as f32];
+                }
+                event::WindowEvent::MouseInput {
+                    state: event::ElementState::Pressed,
+                    button: event::MouseButton::Left,
+                    ..
+                } => {
+                    let particles: Vec<Particle> = match pollster::block_on(
+                        state.buffers.read(&state.display.device, &state.display.queue),
+                    ) {
+                        Ok(particles) => particles,
+                        Err(e) => {
+                            failure = Some(e);
+                            *control_flow = ControlFlow::Exit;
+                            return;
+                        }
+                    };
+                    // projected like the viewport under the cursor was drawn
+                    let [x, y] = cursor;
+                    picked = drawn
+                        .iter()
+                        .find(|(viewport, _)| {
+                            let [left, top, width, height] = viewport.region.map(|v| v as f32);
+                            (left..left + width).contains(&x) && (top..top + height).contains(&y)
+                        })
+                        .and_then(|&(viewport, matrix)| {
+                            let [left, top, width, height] = viewport.region.map(|v| v as f32);
+                            pick::nearest(&particles, matrix, [width, height], [x - left, y - top])
+                        })
+                        .map(|i| (i, pick::host(&particles, i)));
+                    state.scene.post.info.picked = picked.map_or(u32::MAX, |(i, _)| i as u32);
+                    state.scene.post.update(&state.display.queue);
+                    inspection = match picked {
+                        Some((i, host)) => pick::inspect(&particles[i], host.map(|h| &particles[h])),
+                        None => Vec::new(),
+                    };
+                    if inspection.is_empty() {
+                        println!("nothing picked");
+                    }
+                    for line in &inspection {
+                        println!("{}", line);
+                    }
+                }
+                event::WindowEvent::Resized(size)
+                | event::WindowEvent::ScaleFactorChanged {
+                    new_inner_size: &mut size,
+                    ..
+                } => {
+                    state.display.resize(size);
+                    if !state.display.minimized() {
+                        resize_scene(&state.display, &mut state.scene, &layout);
+                    }
                 }
                 _ => {}
             },
@@ -126,12 +509,27 @@ pub async fn run(mut gpu_info: GpuInfo, particles: Vec<Particle>) {
             event::Event::RedrawRequested(_) => {
                 let dt: f32 = update.elapsed().as_secs_f32();
                 update = Instant::now();
-                let surface_texture: SurfaceTexture = state
-                    .display
-                    .surface
-                    .get_current_texture()
-                    .ok()
-                    .expect("no frame texture");
+                // nothing is drawn or simulated while minimized
+                if state.display.minimized() {
+                    return;
+                }
+                let surface_texture: SurfaceTexture =
+                    match state.display.surface.get_current_texture() {
+                        Ok(surface_texture) => surface_texture,
+                        // configured again, the next frame gets a texture
+                        Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+                            state.display.reconfigure();
+                            return;
+                        }
+                        Err(wgpu::SurfaceError::Timeout) => return,
+                        Err(wgpu::SurfaceError::OutOfMemory) => {
+                            failure =
+                                Some(Error::Device("out of memory for the frame texture".into()));
+                            *control_flow = ControlFlow::Exit;
+                            return;
+                        }
+                    };
+                frame_time += (dt - frame_time) * 0.1;
                 let view: wgpu::TextureView = surface_texture
                     .texture
                     .create_view(&wgpu::TextureViewDescriptor::default());
@@ -153,104 +551,301 @@ pub async fn run(mut gpu_info: GpuInfo, particles: Vec<Particle>) {
                     state.display.camera_pos[2],
                 );
 
-                for i in keys.iter() {
-                    match i {
-                        event::VirtualKeyCode::W => {
-                            tmp += cam * vel * dt;
-                        }
-                        event::VirtualKeyCode::A => {
-                            tmp += -right * vel * dt;
+                if rig.mode == CameraMode::Follow && !indexes.is_empty() {
+                    // where the followed particle was a frame or two ago, rather than
+                    // waiting on the gpu for where it is now
+                    let index: usize = indexes[rig.follow % indexes.len()];
+                    match following.take(&state.display.device) {
+                        Ok(Some((i, particles))) if i == index => {
+                            rig.target = particles[0].pos.into()
                         }
-                        event::VirtualKeyCode::S => {
-                            tmp += -cam * vel * dt;
-                        }
-                        event::VirtualKeyCode::D => {
-                            tmp += right * vel * dt;
+                        Ok(_) => {}
+                        Err(e) => {
+                            failure = Some(e);
+                            *control_flow = ControlFlow::Exit;
+                            return;
                         }
-                        event::VirtualKeyCode::Space => {
-                            tmp[1] -= vel * dt;
+                    }
+                } else if rig.mode != CameraMode::Free {
+                    rig.target = match rig_target(
+                        &rig,
+                        &state.buffers,
+                        &state.display,
+                        &state.particles,
+                        &indexes,
+                        time,
+                    ) {
+                        Ok(target) => target,
+                        Err(e) => {
+                            failure = Some(e);
+                            *control_flow = ControlFlow::Exit;
+                            return;
                         }
-                        event::VirtualKeyCode::LShift => {
-                            tmp[1] += vel * dt;
+                    };
+                }
+                if rig.mode != CameraMode::Free {
+                    if bindings.held(&keys, Action::Forward) {
+                        rig.zoom(dt);
+                    }
+                    if bindings.held(&keys, Action::Back) {
+                        rig.zoom(-dt);
+                    }
+                    tmp = rig.position(cam);
+                }
+                if rig.mode == CameraMode::Free {
+                    for (action, direction) in [
+                        (Action::Forward, cam),
+                        (Action::Left, -right),
+                        (Action::Back, -cam),
+                        (Action::Right, right),
+                        (Action::Down, -Vector3::unit_y()),
+                        (Action::Up, Vector3::unit_y()),
+                    ] {
+                        if bindings.held(&keys, action) {
+                            tmp += direction * vel * dt;
                         }
-                        _ => {}
                     }
                 }
-                gpu_info.matrix = build_matrix(
-                    tmp.into(),
-                    cam,
-                    state.display.config.width as f32 / state.display.config.height as f32,
-                )
-                .into();
+                if let Some(view) = camera_path.at(time).filter(|_| playing) {
+                    let View { pos, dir, fovy: path_fovy } = view;
+                    tmp = pos;
+                    cam = dir;
+                    lens.fovy = path_fovy;
+                }
+                let [width, height] =
+                    layout.tile(state.display.config.width, state.display.config.height);
+                let aspect: f32 = width as f32 / height as f32;
+                // the overlays and clipping planes are fit to the rig's target, or the
+                // origin when free
+                lens.distance = if rig.mode == CameraMode::Free {
+                    tmp.to_vec().magnitude()
+                } else {
+                    rig.distance
+                };
+                gpu_info.matrix = build_matrix(tmp, cam, aspect, lens).into();
                 state.display.camera_pos = [tmp[0], tmp[1], tmp[2]];
 
-                let new_gpu_info: wgpu::Buffer =
-                    state
-                        .display
-                        .device
-                        .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                            label: Some("GpuInfo Buffer"),
-                            contents: bytemuck::cast_slice(&[gpu_info]),
-                            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_SRC,
-                        });
-
-                encoder.copy_buffer_to_buffer(
-                    &new_gpu_info,
-                    0,
-                    &state.gpu_buffer,
-                    0,
-                    std::mem::size_of::<GpuInfo>() as u64,
-                );
+                // the replay's particles are uploaded in place of simulating
+                let mut replayed: bool = false;
+                if let Some(replay) = replay.as_mut() {
+                    if !paused {
+                        replay.advance(dt);
+                    }
+                    match replay.frame() {
+                        Ok(Some(snapshot)) => {
+                            state.buffers.upload(
+                                &state.display.device,
+                                &state.display.queue,
+                                &state.gpu_buffer,
+                                &snapshot.particles,
+                            );
+                            gpu_info.particles = snapshot.particles.len() as u32;
+                            time = snapshot.time as f32;
+                            step = snapshot.step as i64;
+                            replayed = true;
+                        }
+                        Ok(None) => {}
+                        Err(e) => eprintln!("failed to read snapshot: {}", e),
+                    }
+                }
+                let steps: u32 = if replay.is_some() {
+                    0
+                } else if paused {
+                    owed = 0.0;
+                    std::mem::take(&mut single) * substeps
+                } else {
+                    owed += rate * dt;
+                    let due: f32 = (owed / motion.abs()).floor();
+                    let most: u32 = MAX_STEPS / substeps;
+                    if due > most as f32 {
+                        owed = 0.0;
+                        most * substeps
+                    } else {
+                        owed -= due * motion.abs();
+                        due as u32 * substeps
+                    }
+                };
+                // each step of dt is taken as `substeps` shorter ones
+                gpu_info.motion = if steps > 0 {
+                    motion / substeps as f32
+                } else {
+                    0.0
+                };
+                state
+                    .display
+                    .queue
+                    .write_buffer(&state.gpu_buffer, 0, bytemuck::cast_slice(&[gpu_info]));
 
-                for _ in 0..3 {
-                    encoder.copy_buffer_to_buffer(&state.cur, 0, &state.prev, 0, p_size);
-                    let mut cpass: wgpu::ComputePass<'_> = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
-                        label: Some("Compute Pass"),
-                    });
-                    cpass.set_pipeline(&state.comp_pipeline);
-                    cpass.set_bind_group(0, &state.bind_group, &[]);
-                    cpass.dispatch_workgroups(workgroups+1, 1, 1);
+                // a copy of the particles from an earlier frame goes to the meter, and
+                // the drift of one before it comes back
+                if let Some((meter, sample)) = drift.as_mut() {
+                    match sample.take(&state.display.device) {
+                        Ok(Some((_, particles))) => meter.measure(particles),
+                        Ok(None) => {}
+                        Err(e) => {
+                            failure = Some(e);
+                            *control_flow = ControlFlow::Exit;
+                            return;
+                        }
+                    }
+                    energy_drift = meter.drift().or(energy_drift);
+                }
+                // once a second, before this frame's steps, which aren't submitted yet;
+                // the reads wait on the gpu, so they aren't done every frame
+                let measuring: bool = measured.elapsed() >= Duration::from_secs(1);
+                if measuring {
+                    measured = Instant::now();
+                    if let Some((index, host)) = picked.filter(|_| state.scene.hud.visible) {
+                        let read = |i: usize| -> Result<Particle, Error> {
+                            Ok(pollster::block_on(state.buffers.read_range(
+                                &state.display.device,
+                                &state.display.queue,
+                                i..i + 1,
+                            ))?[0])
+                        };
+                        match read(index).and_then(|p| Ok((p, host.map(read).transpose()?))) {
+                            Ok((particle, host)) => {
+                                inspection = pick::inspect(&particle, host.as_ref())
+                            }
+                            Err(e) => {
+                                failure = Some(e);
+                                *control_flow = ControlFlow::Exit;
+                                return;
+                            }
+                        }
+                    }
+                }
+                if steps > 0 {
+                    state.kernel.step(&mut encoder, &mut state.buffers, steps);
                 }
-                {
-                    let mut rpass: wgpu::RenderPass<'_> = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                        label: Some("Render Pass"),
-                        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                            view: &view,
-                            resolve_target: None,
-                            ops: wgpu::Operations {
-                                load: wgpu::LoadOp::Clear(wgpu::Color {
-                                    r: 0.03,
-                                    g: 0.03,
-                                    b: 0.03,
-                                    a: 1.0,
-                                }),
-                                store: true,
+                if rig.mode == CameraMode::Follow && !indexes.is_empty() {
+                    let index: usize = indexes[rig.follow % indexes.len()];
+                    following.copy(&mut encoder, &state.buffers, index..index + 1);
+                }
+                // after this frame's steps, once the meter is done with the last copy
+                if let Some((meter, sample)) = drift.as_mut().filter(|_| measuring) {
+                    if !meter.busy() {
+                        sample.copy(&mut encoder, &state.buffers, 0..state.buffers.count);
+                    }
+                }
+                time += steps as f32 * gpu_info.motion;
+                step += steps as i64 * motion.signum() as i64;
+                state.scene.prepare(
+                    &state.display.device,
+                    &state.display.queue,
+                    &mut encoder,
+                    &state.buffers,
+                    steps > 0 || replayed,
+                );
+                let center_of_mass: Point3<f32> = com + com_vel * time;
+                let viewports: Vec<Viewport> = layout
+                    .viewports(state.display.config.width, state.display.config.height);
+                let mut labels: Vec<String> = Vec::new();
+                drawn.clear();
+                state.scene.clear(&mut encoder, &view);
+                for (i, viewport) in viewports.iter().enumerate() {
+                    // buffer writes land before the next submission, so every viewport
+                    // after the first gets its own to see its matrix
+                    if i > 0 {
+                        state.display.queue.submit([encoder.finish()]);
+                        encoder = state.display.device.create_command_encoder(
+                            &wgpu::CommandEncoderDescriptor {
+                                label: Some("Viewport Encoder"),
                             },
-                        })],
-                        depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                            view: &state.depth_view,
-                            depth_ops: Some(wgpu::Operations {
-                                load: wgpu::LoadOp::Clear(1.0),
-                                store: true,
-                            }),
-                            stencil_ops: Some(wgpu::Operations {
-                                load: wgpu::LoadOp::Clear(0),
-                                store: true,
-                            }),
-                        }),
-                    });
-
-                    rpass.set_pipeline(&state.render_pipeline);
-                    rpass.set_bind_group(0, &state.bind_group, &[]);
-                    rpass.draw(0..n as u32, 0..1);
+                        );
+                    }
+                    let mut view_lens: Lens = lens;
+                    let (camera, up) =
+                        match viewport.viewpoint.look(center_of_mass, fixed_distance, lens.fovy) {
+                            Some((camera, up)) => {
+                                view_lens.distance = fixed_distance;
+                                (camera, up)
+                            }
+                            None => (
+                                View {
+                                    pos: tmp,
+                                    dir: cam,
+                                    fovy: lens.fovy,
+                                },
+                                Vector3::unit_y(),
+                            ),
+                        };
+                    let matrix: Matrix4<f32> =
+                        build_matrix_up(camera.pos, camera.dir, up, aspect, view_lens);
+                    drawn.push((*viewport, matrix));
+                    let mut info: GpuInfo = gpu_info;
+                    info.matrix = matrix.into();
+                    state
+                        .display
+                        .queue
+                        .write_buffer(&state.gpu_buffer, 0, bytemuck::cast_slice(&[info]));
+                    state.scene.post.set_lens(&view_lens);
+                    state.scene.post.update(&state.display.queue);
+                    state.scene.overlay.update(
+                        &state.display.queue,
+                        &camera,
+                        up,
+                        view_lens.distance,
+                        center_of_mass,
+                    );
+                    labels.extend(state.scene.overlay.labels().into_iter().map(|label| {
+                        if layout.split {
+                            format!("{} {}", viewport.viewpoint.describe(), label)
+                        } else {
+                            label
+                        }
+                    }));
+                    state.scene.draw_view(
+                        &state.display.queue,
+                        &mut encoder,
+                        &state.buffers,
+                        &view,
+                        viewport.region,
+                    );
+                }
+                // one line under the bottom left viewport's scale bar
+                let labels: Vec<String> = if labels.is_empty() {
+                    labels
+                } else {
+                    vec![labels.join("  ")]
+                };
+                let mut lines: Vec<String> = Vec::new();
+                if state.scene.hud.visible {
+                    let stats: Stats = Stats {
+                        time,
+                        step,
+                        dt: motion,
+                        substeps,
+                        rate: Some(rate),
+                        method: state.kernel.integrator.method(),
+                        paused,
+                        particles: state.buffers.count,
+                        frame_time: Some(frame_time),
+                        steps_per_second: Some(steps as f32 / frame_time),
+                        camera: state.display.camera_pos,
+                        energy_drift,
+                        timeline: replay.as_ref().map(Replay::timeline),
+                        inspection: inspection.clone(),
+                    };
+                    lines = stats.lines();
                 }
+                state.scene.hud.set_text(&state.display.queue, &lines, &labels);
+                state.scene.hud.draw(&mut encoder, &view);
                 drop(view);
                 state.display.queue.submit([encoder.finish()]);
+                following.map();
+                if let Some((_, sample)) = drift.as_mut() {
+                    sample.map();
+                }
+                let suboptimal: bool = surface_texture.suboptimal;
                 surface_texture.present();
-                state
-                    .display
-                    .surface
-                    .configure(&state.display.device, &state.display.config);
+                if suboptimal {
+                    state.display.reconfigure();
+                }
+                if let Err(e) = state.display.failures.check() {
+                    failure = Some(e);
+                    *control_flow = ControlFlow::Exit;
+                }
             }
             event::Event::MainEventsCleared => {
                 state.display.window.request_redraw();
@@ -258,4 +853,5 @@ pub async fn run(mut gpu_info: GpuInfo, particles: Vec<Particle>) {
             _ => {}
         }
     });
+    failure.map_or(Ok(()), Err)
 }
@@ -0,0 +1,274 @@
+use {
+    serde::{de, Deserialize, Deserializer, Serialize},
+    std::{
+        collections::{HashMap, HashSet},
+        fs, io,
+        path::Path,
+    },
+    winit::event::VirtualKeyCode,
+};
+
+// what the window's keys do, a replay takes the time controls over to move
+// through its snapshots
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Action {
+    Forward,
+    Left,
+    Back,
+    Right,
+    Up,
+    Down,
+    // releases the captured cursor first
+    Quit,
+    Pause,
+    Resume,
+    Step,
+    // double and halve dt
+    Faster,
+    Slower,
+    // fewer and more steps each step of dt is split into
+    FewerSteps,
+    MoreSteps,
+    // halve and double the simulated time per second
+    SlowerRate,
+    FasterRate,
+    Reverse,
+    Restart,
+    Keyframe,
+    PlayPath,
+    FreeCamera,
+    OrbitCamera,
+    FollowCamera,
+    CenterOfMassCamera,
+    NextTarget,
+    Track,
+    Orthographic,
+    // see color::Coloring::act
+    ColorMode,
+    Colormap,
+    RadiusCenter,
+    LogScale,
+    LowerMin,
+    RaiseMin,
+    LowerMax,
+    RaiseMax,
+    FitRange,
+    Bloom,
+    LessExposure,
+    MoreExposure,
+    Trails,
+    Projection,
+    FewerDecades,
+    MoreDecades,
+    Grid,
+    Axes,
+    ScaleBar,
+    Markers,
+    SplitView,
+    Hud,
+    // only in a replay
+    LastSnapshot,
+    Interpolate,
+}
+
+const DEFAULTS: [(Action, VirtualKeyCode); 51] = [
+    (Action::Forward, VirtualKeyCode::W),
+    (Action::Left, VirtualKeyCode::A),
+    (Action::Back, VirtualKeyCode::S),
+    (Action::Right, VirtualKeyCode::D),
+    (Action::Up, VirtualKeyCode::LShift),
+    (Action::Down, VirtualKeyCode::Space),
+    (Action::Quit, VirtualKeyCode::Escape),
+    (Action::Pause, VirtualKeyCode::P),
+    (Action::Resume, VirtualKeyCode::R),
+    (Action::Step, VirtualKeyCode::N),
+    (Action::Faster, VirtualKeyCode::Up),
+    (Action::Slower, VirtualKeyCode::Down),
+    (Action::FewerSteps, VirtualKeyCode::Left),
+    (Action::MoreSteps, VirtualKeyCode::Right),
+    (Action::SlowerRate, VirtualKeyCode::Q),
+    (Action::FasterRate, VirtualKeyCode::E),
+    (Action::Reverse, VirtualKeyCode::Back),
+    (Action::Restart, VirtualKeyCode::Home),
+    (Action::Keyframe, VirtualKeyCode::K),
+    (Action::PlayPath, VirtualKeyCode::Y),
+    (Action::FreeCamera, VirtualKeyCode::Key1),
+    (Action::OrbitCamera, VirtualKeyCode::Key2),
+    (Action::FollowCamera, VirtualKeyCode::Key3),
+    (Action::CenterOfMassCamera, VirtualKeyCode::Key4),
+    (Action::NextTarget, VirtualKeyCode::Tab),
+    (Action::Track, VirtualKeyCode::G),
+    (Action::Orthographic, VirtualKeyCode::O),
+    (Action::ColorMode, VirtualKeyCode::C),
+    (Action::Colormap, VirtualKeyCode::V),
+    (Action::RadiusCenter, VirtualKeyCode::X),
+    (Action::LogScale, VirtualKeyCode::L),
+    (Action::LowerMin, VirtualKeyCode::LBracket),
+    (Action::RaiseMin, VirtualKeyCode::RBracket),
+    (Action::LowerMax, VirtualKeyCode::Semicolon),
+    (Action::RaiseMax, VirtualKeyCode::Apostrophe),
+    (Action::FitRange, VirtualKeyCode::Backslash),
+    (Action::Bloom, VirtualKeyCode::B),
+    (Action::LessExposure, VirtualKeyCode::Minus),
+    (Action::MoreExposure, VirtualKeyCode::Equals),
+    (Action::Trails, VirtualKeyCode::T),
+    (Action::Projection, VirtualKeyCode::M),
+    (Action::FewerDecades, VirtualKeyCode::Comma),
+    (Action::MoreDecades, VirtualKeyCode::Period),
+    (Action::Grid, VirtualKeyCode::F1),
+    (Action::Axes, VirtualKeyCode::F2),
+    (Action::ScaleBar, VirtualKeyCode::F3),
+    (Action::Markers, VirtualKeyCode::F4),
+    (Action::SplitView, VirtualKeyCode::F5),
+    (Action::Hud, VirtualKeyCode::H),
+    (Action::LastSnapshot, VirtualKeyCode::End),
+    (Action::Interpolate, VirtualKeyCode::I),
+];
+
+fn invalid(e: impl std::fmt::Display) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e.to_string())
+}
+
+// the actions a bindings file changes, which a plain map would let name one twice
+// and keep only the last
+struct Changes(HashMap<Action, VirtualKeyCode>);
+
+impl<'de> Deserialize<'de> for Changes {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Visitor;
+
+        impl<'de> de::Visitor<'de> for Visitor {
+            type Value = Changes;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.write_str("a map of actions to keys")
+            }
+
+            fn visit_map<A: de::MapAccess<'de>>(self, mut map: A) -> Result<Changes, A::Error> {
+                let mut keys: HashMap<Action, VirtualKeyCode> = HashMap::new();
+                while let Some((action, key)) = map.next_entry::<Action, VirtualKeyCode>()? {
+                    if keys.insert(action, key).is_some() {
+                        return Err(de::Error::custom(format!("{:?} is bound twice", action)));
+                    }
+                }
+                Ok(Changes(keys))
+            }
+        }
+
+        deserializer.deserialize_map(Visitor)
+    }
+}
+
+// a key per action, the defaults above with what a ron map of action to key
+// names, like `{Forward: Up, Faster: PageUp}`, changes
+#[derive(Clone, Debug)]
+pub struct Bindings {
+    keys: HashMap<Action, VirtualKeyCode>,
+    actions: HashMap<VirtualKeyCode, Action>,
+}
+
+impl Bindings {
+    fn new(keys: HashMap<Action, VirtualKeyCode>) -> io::Result<Self> {
+        let mut actions: HashMap<VirtualKeyCode, Action> = HashMap::new();
+        for (&action, &key) in &keys {
+            if let Some(other) = actions.insert(key, action) {
+                return Err(invalid(format!(
+                    "{:?} is bound to both {:?} and {:?}",
+                    key, action, other
+                )));
+            }
+        }
+        Ok(Self { keys, actions })
+    }
+
+    // the defaults with the changes in `text`
+    fn parse(text: &str) -> io::Result<Self> {
+        let changes: Changes = ron::from_str(text).map_err(invalid)?;
+        let mut keys: HashMap<Action, VirtualKeyCode> = DEFAULTS.into_iter().collect();
+        keys.extend(changes.0);
+        Self::new(keys)
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    // the defaults when the file doesn't exist
+    pub fn load_or_default(path: &Path) -> io::Result<Self> {
+        match Self::load(path) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                Self::new(DEFAULTS.into_iter().collect())
+            }
+            result => result,
+        }
+    }
+
+    pub fn action(&self, key: VirtualKeyCode) -> Option<Action> {
+        self.actions.get(&key).copied()
+    }
+
+    // whether the action's key is among the `held` ones
+    pub fn held(&self, held: &HashSet<VirtualKeyCode>, action: Action) -> bool {
+        held.contains(&self.keys[&action])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn changes_override_only_their_actions() {
+        let bindings: Bindings = Bindings::parse("{Forward: Up, Faster: PageUp}").unwrap();
+        assert_eq!(bindings.action(VirtualKeyCode::Up), Some(Action::Forward));
+        let faster: Option<Action> = bindings.action(VirtualKeyCode::PageUp);
+        assert_eq!(faster, Some(Action::Faster));
+        assert_eq!(bindings.action(VirtualKeyCode::W), None);
+        assert_eq!(bindings.action(VirtualKeyCode::P), Some(Action::Pause));
+        let held: HashSet<VirtualKeyCode> = [VirtualKeyCode::Up].into();
+        assert!(bindings.held(&held, Action::Forward));
+        assert!(!bindings.held(&held, Action::Faster));
+    }
+
+    #[test]
+    fn a_key_bound_twice_is_an_error() {
+        // P still pauses
+        let e: io::Error = Bindings::parse("{Step: P}").unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+        assert!(e.to_string().contains("bound to both"), "{}", e);
+    }
+
+    #[test]
+    fn an_action_named_twice_is_an_error() {
+        let e: io::Error = Bindings::parse("{Forward: Up, Forward: PageUp}").unwrap_err();
+        assert!(e.to_string().contains("Forward is bound twice"), "{}", e);
+    }
+
+    #[test]
+    fn module_keys_are_taken_too() {
+        // C cycles the color mode
+        let e: io::Error = Bindings::parse("{Forward: C}").unwrap_err();
+        assert!(e.to_string().contains("ColorMode"), "{}", e);
+        let bindings: Bindings = Bindings::parse("{Hud: F12}").unwrap();
+        assert_eq!(bindings.action(VirtualKeyCode::F12), Some(Action::Hud));
+        assert_eq!(bindings.action(VirtualKeyCode::H), None);
+    }
+
+    #[test]
+    fn swapping_two_keys_is_fine() {
+        let bindings: Bindings = Bindings::parse("{Pause: R, Resume: P}").unwrap();
+        assert_eq!(bindings.action(VirtualKeyCode::R), Some(Action::Pause));
+        assert_eq!(bindings.action(VirtualKeyCode::P), Some(Action::Resume));
+    }
+
+    #[test]
+    fn unknown_names_are_errors() {
+        let e: io::Error = Bindings::parse("{Jump: Space}").unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn defaults_bind_every_action_once() {
+        assert_eq!(Bindings::parse("{}").unwrap().actions.len(), DEFAULTS.len());
+    }
+}
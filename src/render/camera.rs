@@ -0,0 +1,363 @@
+use {
+    crate::Particle,
+    cgmath::{prelude::*, Deg, Point3, Rad, Vector3},
+    serde::{Deserialize, Serialize},
+    std::{fs, io, path::Path},
+};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct Keyframe {
+    // simulated time, steps times GpuInfo::motion
+    pub time: f32,
+    pub position: [f32; 3],
+    pub look_at: [f32; 3],
+    // vertical field of view in degrees
+    pub fov: f32,
+}
+
+// where the camera is, where it looks and its vertical field of view in radians
+#[derive(Clone, Copy, Debug)]
+pub struct View {
+    pub pos: Point3<f32>,
+    pub dir: Vector3<f32>,
+    pub fovy: f32,
+}
+
+// keyframes sorted by time, interpolated with a cubic hermite spline whose tangents
+// come from the neighbouring keyframes (catmull-rom for uneven spacing)
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct CameraPath {
+    pub keyframes: Vec<Keyframe>,
+}
+
+fn invalid(e: impl std::fmt::Display) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e.to_string())
+}
+
+// position, look-at and fov as one vector
+fn pack(key: &Keyframe) -> [f32; 7] {
+    let [x, y, z] = key.position;
+    let [a, b, c] = key.look_at;
+    [x, y, z, a, b, c, key.fov]
+}
+
+impl CameraPath {
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let mut camera_path: CameraPath = ron::from_str(&fs::read_to_string(path)?).map_err(invalid)?;
+        camera_path
+            .keyframes
+            .sort_by(|a, b| a.time.total_cmp(&b.time));
+        Ok(camera_path)
+    }
+
+    // an empty path when the file doesn't exist yet
+    pub fn load_or_default(path: &Path) -> io::Result<Self> {
+        match Self::load(path) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            result => result,
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let text: String =
+            ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default()).map_err(invalid)?;
+        fs::write(path, text)
+    }
+
+    // adds a keyframe in time order, replacing one at the same time
+    pub fn insert(&mut self, key: Keyframe) {
+        let at: usize = self.keyframes.partition_point(|k| k.time < key.time);
+        if self.keyframes.get(at).is_some_and(|k| k.time == key.time) {
+            self.keyframes[at] = key;
+        } else {
+            self.keyframes.insert(at, key);
+        }
+    }
+
+    // the camera at `time`, held at the first and last keyframe outside their range
+    pub fn at(&self, time: f32) -> Option<View> {
+        let keys: &[Keyframe] = &self.keyframes;
+        let (first, last) = (keys.first()?, keys.last()?);
+        let values: [f32; 7] = if time <= first.time {
+            pack(first)
+        } else if time >= last.time {
+            pack(last)
+        } else {
+            let k: usize = keys.partition_point(|key| key.time <= time) - 1;
+            let tangent = |i: usize| -> [f32; 7] {
+                let (a, b) = (i.saturating_sub(1), (i + 1).min(keys.len() - 1));
+                let (pa, pb) = (pack(&keys[a]), pack(&keys[b]));
+                let dt: f32 = keys[b].time - keys[a].time;
+                std::array::from_fn(|j| (pb[j] - pa[j]) / dt)
+            };
+            let (p0, p1) = (pack(&keys[k]), pack(&keys[k + 1]));
+            let (m0, m1) = (tangent(k), tangent(k + 1));
+            let h: f32 = keys[k + 1].time - keys[k].time;
+            let s: f32 = (time - keys[k].time) / h;
+            let (s2, s3) = (s * s, s * s * s);
+            std::array::from_fn(|j| {
+                (2.0 * s3 - 3.0 * s2 + 1.0) * p0[j]
+                    + (s3 - 2.0 * s2 + s) * h * m0[j]
+                    + (-2.0 * s3 + 3.0 * s2) * p1[j]
+                    + (s3 - s2) * h * m1[j]
+            })
+        };
+        let pos: Point3<f32> = Point3::new(values[0], values[1], values[2]);
+        let dir: Vector3<f32> = Point3::new(values[3], values[4], values[5]) - pos;
+        Some(View {
+            pos,
+            dir: if dir.magnitude2() > 0.0 {
+                dir.normalize()
+            } else {
+                -Vector3::unit_z()
+            },
+            fovy: Rad::from(Deg(values[6])).0,
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mode {
+    // WASD and the mouse move the camera freely
+    Free,
+    // the mouse turns the camera around a fixed point, W and S zoom
+    Orbit,
+    // like Orbit around one of the tracked particles
+    Follow,
+    // like Orbit around the center of mass of the system
+    CenterOfMass,
+}
+
+impl Mode {
+    pub fn describe(&self) -> &'static str {
+        match self {
+            Mode::Free => "free camera",
+            Mode::Orbit => "orbit camera",
+            Mode::Follow => "follow camera",
+            Mode::CenterOfMass => "center of mass camera",
+        }
+    }
+}
+
+// what the camera circles outside of Mode::Free, it stays `distance` from the
+// target along the view direction
+#[derive(Clone, Copy, Debug)]
+pub struct Rig {
+    pub mode: Mode,
+    pub target: Point3<f32>,
+    pub distance: f32,
+    // index into the tracked particles of the one followed
+    pub follow: usize,
+}
+
+impl Rig {
+    pub fn new() -> Self {
+        Self {
+            mode: Mode::Free,
+            target: Point3::origin(),
+            distance: 1.0,
+            follow: 0,
+        }
+    }
+
+    // switches mode, keeping the camera where it is; `target` is the new target
+    // for Follow and CenterOfMass, while Orbit picks the point ahead of the camera
+    // as far as the camera is from the origin
+    pub fn set_mode(&mut self, mode: Mode, pos: Point3<f32>, dir: Vector3<f32>, target: Point3<f32>) {
+        self.mode = mode;
+        self.target = match mode {
+            Mode::Orbit => pos + dir * pos.to_vec().magnitude(),
+            _ => target,
+        };
+        self.distance = (self.target - pos).magnitude().max(f32::MIN_POSITIVE);
+    }
+
+    // scales the distance by e^-amount, zooming in for positive amounts
+    pub fn zoom(&mut self, amount: f32) {
+        self.distance *= (-amount).exp();
+    }
+
+    pub fn position(&self, dir: Vector3<f32>) -> Point3<f32> {
+        self.target - dir * self.distance
+    }
+}
+
+// extents past the scene the clipping planes reach, particles are flung well out
+const REACH: f32 = 10.0;
+// far over near for perspective when the camera is inside the scene
+const DEPTH_RATIO: f32 = 1e6;
+// the field of view the mouse wheel stays within, in degrees
+const MIN_FOV: f32 = 1.0;
+const MAX_FOV: f32 = 150.0;
+
+// how views are projected: perspective with a vertical field of view, or
+// orthographic as wide as the perspective view is at `distance`, the distance
+// to what is looked at; near and far hug a scene of radius `extent` there
+#[derive(Clone, Copy, Debug)]
+pub struct Lens {
+    pub fovy: f32,
+    pub orthographic: bool,
+    pub distance: f32,
+    pub extent: f32,
+}
+
+impl Lens {
+    pub fn new(fovy: f32, extent: f32) -> Self {
+        Self {
+            fovy,
+            orthographic: false,
+            distance: extent,
+            extent,
+        }
+    }
+
+    // half the view's height at `distance`, all of it when orthographic
+    pub fn half_height(&self) -> f32 {
+        self.distance * (self.fovy / 2.0).tan()
+    }
+
+    // world units to clip space at unit depth, what sprites are sized with
+    pub fn scale(&self) -> f32 {
+        if self.orthographic {
+            1.0 / self.half_height()
+        } else {
+            1.0 / (self.fovy / 2.0).tan()
+        }
+    }
+
+    // orthographic views keep what is behind the camera, a projection of the whole
+    // scene, while perspective ones keep near in front of it
+    pub fn clip_planes(&self) -> (f32, f32) {
+        let far: f32 = self.distance + REACH * self.extent;
+        let near: f32 = self.distance - REACH * self.extent;
+        if self.orthographic {
+            (near, far)
+        } else {
+            (near.max(far / DEPTH_RATIO), far)
+        }
+    }
+
+    // the mouse wheel, positive `lines` narrow the field of view
+    pub fn zoom(&mut self, lines: f32) {
+        let fov: f32 = Deg::from(Rad(self.fovy)).0 * (-0.1 * lines).exp();
+        self.fovy = Rad::from(Deg(fov.clamp(MIN_FOV, MAX_FOV))).0;
+    }
+
+    pub fn describe(&self) -> String {
+        format!(
+            "{} projection, field of view {:.1} degrees",
+            if self.orthographic {
+                "orthographic"
+            } else {
+                "perspective"
+            },
+            Deg::from(Rad(self.fovy)).0
+        )
+    }
+}
+
+// how far the particles reach from their center of mass, at least `fallback`
+pub fn extent(particles: &[Particle], fallback: f32) -> f32 {
+    let (center, _) = center_of_mass(particles);
+    let radius: f32 = particles
+        .iter()
+        .map(|p| (Point3::from(p.pos) - center).magnitude())
+        .fold(0.0, f32::max);
+    if radius > 0.0 {
+        radius
+    } else {
+        fallback
+    }
+}
+
+// the center of mass and its velocity, which the simulation conserves
+pub fn center_of_mass(particles: &[Particle]) -> (Point3<f32>, Vector3<f32>) {
+    let mut mass: f64 = 0.0;
+    let mut pos: [f64; 3] = [0.0; 3];
+    let mut vel: [f64; 3] = [0.0; 3];
+    for p in particles {
+        mass += p.mass as f64;
+        for k in 0..3 {
+            pos[k] += p.mass as f64 * p.pos[k] as f64;
+            vel[k] += p.mass as f64 * p.vel[k] as f64;
+        }
+    }
+    if mass == 0.0 {
+        return (Point3::origin(), Vector3::zero());
+    }
+    (
+        Point3::new(pos[0], pos[1], pos[2]).map(|c| (c / mass) as f32),
+        Vector3::new(vel[0], vel[1], vel[2]).map(|v| (v / mass) as f32),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(time: f32, x: f32) -> Keyframe {
+        Keyframe {
+            time,
+            position: [x, 2.0 * x, 5.0],
+            look_at: [x, 0.0, 0.0],
+            fov: 30.0 + x,
+        }
+    }
+
+    fn path(keys: &[Keyframe]) -> CameraPath {
+        let mut path: CameraPath = CameraPath::default();
+        for &key in keys {
+            path.insert(key);
+        }
+        path
+    }
+
+    fn assert_at(path: &CameraPath, time: f32, key: Keyframe) {
+        let view: View = path.at(time).unwrap();
+        assert_eq!(view.pos, Point3::from(key.position), "at {}", time);
+        let dir: Vector3<f32> = Point3::from(key.look_at) - Point3::from(key.position);
+        let dir: Vector3<f32> = dir.normalize();
+        assert!((view.dir - dir).magnitude() < 1e-6, "at {}", time);
+        assert_eq!(view.fovy, Rad::from(Deg(key.fov)).0, "at {}", time);
+    }
+
+    #[test]
+    fn insert_keeps_time_order_and_replaces_equal_times() {
+        let path: CameraPath = path(&[key(2.0, 2.0), key(0.0, 0.0), key(1.0, 1.0), key(1.0, 7.0)]);
+        let times: Vec<f32> = path.keyframes.iter().map(|k| k.time).collect();
+        assert_eq!(times, [0.0, 1.0, 2.0]);
+        assert_eq!(path.keyframes[1].position, key(1.0, 7.0).position);
+    }
+
+    #[test]
+    fn keyframes_are_reproduced_at_their_times() {
+        let keys: [Keyframe; 4] = [key(0.0, 0.0), key(1.0, 3.0), key(3.0, -2.0), key(4.0, 1.0)];
+        let path: CameraPath = path(&keys);
+        for key in keys {
+            assert_at(&path, key.time, key);
+        }
+    }
+
+    #[test]
+    fn held_outside_the_range() {
+        let (first, last) = (key(1.0, 1.0), key(2.0, 4.0));
+        let path: CameraPath = path(&[first, last]);
+        assert_at(&path, -10.0, first);
+        assert_at(&path, 0.5, first);
+        assert_at(&path, 2.5, last);
+        assert_at(&path, 100.0, last);
+    }
+
+    #[test]
+    fn two_keyframes_are_a_straight_line() {
+        let path: CameraPath = path(&[key(0.0, 0.0), key(2.0, 4.0)]);
+        let view: View = path.at(1.0).unwrap();
+        assert!((view.pos - Point3::new(2.0, 4.0, 5.0)).magnitude() < 1e-5);
+        assert!((view.fovy - Rad::from(Deg(32.0)).0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn empty_path_has_no_view() {
+        assert!(CameraPath::default().at(0.0).is_none());
+    }
+}
@@ -0,0 +1,460 @@
+use {
+    crate::{buffers::ParticleBuffers, render::bindings::Action, Particle},
+    cgmath::{prelude::*, Vector3},
+    wgpu::util::DeviceExt,
+};
+
+pub const MODES: [&str; 6] = [
+    "component",
+    "speed",
+    "kinetic energy",
+    "density",
+    "radius",
+    "id",
+];
+const COMPONENT: u32 = 0;
+const SPEED: u32 = 1;
+const KINETIC_ENERGY: u32 = 2;
+const DENSITY: u32 = 3;
+const RADIUS: u32 = 4;
+
+const STOPS: usize = 9;
+// evenly spaced samples of each map, interpolated linearly in draw.wgsl
+pub const COLORMAPS: [(&str, [u32; STOPS]); 6] = [
+    (
+        "viridis",
+        [
+            0x440154, 0x472D7B, 0x3B528B, 0x2C728E, 0x21908C, 0x27AD81, 0x5DC863, 0xAADC32,
+            0xFDE725,
+        ],
+    ),
+    (
+        "magma",
+        [
+            0x000004, 0x1D1147, 0x51127C, 0x832681, 0xB73779, 0xE75263, 0xFC8961, 0xFEC488,
+            0xFCFDBF,
+        ],
+    ),
+    (
+        "inferno",
+        [
+            0x000004, 0x1F0C48, 0x550F6D, 0x88226A, 0xBA3655, 0xE35932, 0xF98C0A, 0xF9C932,
+            0xFCFFA4,
+        ],
+    ),
+    (
+        "plasma",
+        [
+            0x0D0887, 0x4C02A1, 0x7E03A8, 0xA92395, 0xCC4778, 0xE56B5D, 0xF89441, 0xFDC328,
+            0xF0F921,
+        ],
+    ),
+    (
+        "turbo",
+        [
+            0x30123B, 0x4662D7, 0x36AAF9, 0x1AE4B6, 0x72FE5E, 0xC8EF34, 0xFABA39, 0xF66B19,
+            0x7A0403,
+        ],
+    ),
+    (
+        "grey",
+        [
+            0x000000, 0x202020, 0x404040, 0x606060, 0x808080, 0x9F9F9F, 0xBFBFBF, 0xDFDFDF,
+            0xFFFFFF,
+        ],
+    ),
+];
+
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+pub struct ColorInfo {
+    pub mode: u32,
+    pub colormap: u32,
+    // particle to measure radius from, u32::MAX for the origin
+    pub center: u32,
+    // 1 maps log10 of the value onto the range
+    pub log: u32,
+    pub min: f32,
+    pub max: f32,
+    // smoothing radius of the density estimate
+    pub radius: f32,
+    _pad: f32,
+}
+
+// runtime-selectable particle coloring, group 1 of the draw and density shaders
+pub struct Coloring {
+    pub info: ColorInfo,
+    // particles that RadiusCenter cycles the radius center through
+    pub centers: Vec<u32>,
+    // the range waits for fit_range with the particles as they are now
+    pub stale: bool,
+    pub count: usize,
+    pub buffer: wgpu::Buffer,
+    pub colormaps: wgpu::Buffer,
+    pub density: wgpu::Buffer,
+    pub bind_group_layout: wgpu::BindGroupLayout,
+    pub bind_group: wgpu::BindGroup,
+    // the density pass writes the estimate, the draw only reads it
+    density_layout: wgpu::BindGroupLayout,
+    density_bind_group: wgpu::BindGroup,
+    pub density_pipeline: wgpu::ComputePipeline,
+}
+
+fn create_density(device: &wgpu::Device, count: usize) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        size: (count.max(1) * std::mem::size_of::<f32>()) as u64,
+        usage: wgpu::BufferUsages::STORAGE,
+        label: Some("Density Buffer"),
+        mapped_at_creation: false,
+    })
+}
+
+fn info_entry(visibility: wgpu::ShaderStages) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding: 0,
+        visibility,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<ColorInfo>() as _),
+        },
+        count: None,
+    }
+}
+
+fn storage_entry(
+    binding: u32,
+    visibility: wgpu::ShaderStages,
+    read_only: bool,
+) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only },
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    }
+}
+
+fn create_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    buffer: &wgpu::Buffer,
+    colormaps: &wgpu::Buffer,
+    density: &wgpu::Buffer,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("Color Bind Group"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: colormaps.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: density.as_entire_binding(),
+            },
+        ],
+    })
+}
+
+fn create_density_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    buffer: &wgpu::Buffer,
+    density: &wgpu::Buffer,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("Density Bind Group"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: density.as_entire_binding(),
+            },
+        ],
+    })
+}
+
+// 1st and 99th percentile, ignoring values log scaling can't show
+fn percentiles(mut values: Vec<f32>, log: bool) -> (f32, f32) {
+    values.retain(|v| v.is_finite() && (!log || *v > 0.0));
+    if values.is_empty() {
+        return (0.0, 1.0);
+    }
+    values.sort_by(f32::total_cmp);
+    let lo: f32 = values[values.len() / 100];
+    let hi: f32 = values[values.len() - 1 - values.len() / 100];
+    if hi > lo {
+        (lo, hi)
+    } else {
+        (lo, lo + 1.0)
+    }
+}
+
+impl Coloring {
+    pub fn new(
+        device: &wgpu::Device,
+        particle_layout: &wgpu::BindGroupLayout,
+        particles: &[Particle],
+        centers: Vec<u32>,
+    ) -> Self {
+        // a hundredth of the initial extent
+        let extent: f32 = particles
+            .iter()
+            .map(|p| Vector3::from(p.pos).magnitude())
+            .fold(0.0, f32::max);
+        let info: ColorInfo = ColorInfo {
+            mode: COMPONENT,
+            colormap: 0,
+            center: u32::MAX,
+            log: 0,
+            min: 0.0,
+            max: 1.0,
+            radius: (extent / 100.0).max(f32::MIN_POSITIVE),
+            _pad: 0.0,
+        };
+        let buffer: wgpu::Buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("ColorInfo Buffer"),
+            contents: bytemuck::cast_slice(&[info]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let stops: Vec<[f32; 4]> = COLORMAPS
+            .iter()
+            .flat_map(|(_, map)| map.iter())
+            .map(|rgb| {
+                let channel = |shift: u32| ((rgb >> shift) & 0xFF) as f32 / 255.0;
+                [channel(16), channel(8), channel(0), 1.0]
+            })
+            .collect();
+        let colormaps: wgpu::Buffer =
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Colormap Buffer"),
+                contents: bytemuck::cast_slice(&stops),
+                usage: wgpu::BufferUsages::STORAGE,
+            });
+        let density: wgpu::Buffer = create_density(device, particles.len());
+
+        let bind_group_layout: wgpu::BindGroupLayout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Color Bind Group Layout"),
+                entries: &[
+                    info_entry(wgpu::ShaderStages::VERTEX),
+                    storage_entry(1, wgpu::ShaderStages::VERTEX, true),
+                    storage_entry(2, wgpu::ShaderStages::VERTEX, true),
+                ],
+            });
+        let bind_group: wgpu::BindGroup =
+            create_bind_group(device, &bind_group_layout, &buffer, &colormaps, &density);
+        let density_layout: wgpu::BindGroupLayout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Density Bind Group Layout"),
+                entries: &[
+                    info_entry(wgpu::ShaderStages::COMPUTE),
+                    storage_entry(2, wgpu::ShaderStages::COMPUTE, false),
+                ],
+            });
+        let density_bind_group: wgpu::BindGroup =
+            create_density_bind_group(device, &density_layout, &buffer, &density);
+
+        let source: &str = include_str!("../shaders/density.wgsl");
+        let module: wgpu::ShaderModule =
+            device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("Density Shader"),
+                source: wgpu::ShaderSource::Wgsl(source.into()),
+            });
+        let pipeline_layout: wgpu::PipelineLayout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Density Pipeline Layout"),
+                bind_group_layouts: &[particle_layout, &density_layout],
+                push_constant_ranges: &[],
+            });
+        let density_pipeline: wgpu::ComputePipeline =
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some("Density Pipeline"),
+                module: &module,
+                entry_point: "main",
+                layout: Some(&pipeline_layout),
+            });
+
+        Self {
+            info,
+            centers,
+            stale: false,
+            count: particles.len(),
+            buffer,
+            colormaps,
+            density,
+            bind_group_layout,
+            bind_group,
+            density_layout,
+            density_bind_group,
+            density_pipeline,
+        }
+    }
+
+    // the quantity draw.wgsl colors by, for fitting the range on the cpu
+    fn value(&self, particles: &[Particle], i: usize) -> f32 {
+        let p: &Particle = &particles[i];
+        let speed2: f32 = Vector3::from(p.vel).magnitude2();
+        match self.info.mode {
+            SPEED => speed2.sqrt(),
+            KINETIC_ENERGY => 0.5 * p.mass * speed2,
+            DENSITY => {
+                let h2: f32 = self.info.radius * self.info.radius;
+                particles
+                    .iter()
+                    .map(|q| {
+                        let r2: f32 = (Vector3::from(q.pos) - Vector3::from(p.pos)).magnitude2();
+                        let w: f32 = (1.0 - r2 / h2).max(0.0);
+                        q.mass * w * w * w
+                    })
+                    .sum()
+            }
+            RADIUS => {
+                let center: Vector3<f32> = particles
+                    .get(self.info.center as usize)
+                    .map_or(Vector3::zero(), |c| c.pos.into());
+                (Vector3::from(p.pos) - center).magnitude()
+            }
+            _ => p.id as f32,
+        }
+    }
+
+    // fits min/max to the given particles, sampling when the value is expensive
+    pub fn fit_range(&mut self, particles: &[Particle]) {
+        let stride: usize = if self.info.mode == DENSITY {
+            (particles.len() / 256).max(1)
+        } else {
+            1
+        };
+        let values: Vec<f32> = (0..particles.len())
+            .step_by(stride)
+            .map(|i| self.value(particles, i))
+            .collect();
+        (self.info.min, self.info.max) = percentiles(values, self.info.log == 1);
+        self.stale = false;
+    }
+
+    // moves one end of the range by a twentieth of its span, in log space if enabled
+    fn nudge(&mut self, max: bool, up: bool) {
+        let log: bool = self.info.log == 1 && self.info.min > 0.0;
+        let (lo, hi) = if log {
+            (self.info.min.log10(), self.info.max.log10())
+        } else {
+            (self.info.min, self.info.max)
+        };
+        let step: f32 = (hi - lo) / 20.0 * if up { 1.0 } else { -1.0 };
+        let end: f32 = if max { hi + step } else { lo + step };
+        let end: f32 = if log { 10f32.powf(end) } else { end };
+        if max {
+            self.info.max = end;
+        } else {
+            self.info.min = end;
+        }
+    }
+
+    // returns whether the action changed the coloring, a range to refit is left stale
+    pub fn act(&mut self, action: Action) -> bool {
+        match action {
+            Action::ColorMode => {
+                self.info.mode = (self.info.mode + 1) % MODES.len() as u32;
+                self.info.log =
+                    (self.info.mode == KINETIC_ENERGY || self.info.mode == DENSITY) as u32;
+                self.stale = true;
+            }
+            Action::Colormap => {
+                self.info.colormap = (self.info.colormap + 1) % COLORMAPS.len() as u32;
+            }
+            Action::RadiusCenter => {
+                let next: usize = self
+                    .centers
+                    .iter()
+                    .position(|&c| c == self.info.center)
+                    .map_or(0, |i| i + 1);
+                self.info.center = self.centers.get(next).copied().unwrap_or(u32::MAX);
+                self.stale = self.info.mode == RADIUS;
+            }
+            Action::LogScale => {
+                self.info.log ^= 1;
+                self.stale = true;
+            }
+            Action::LowerMin => self.nudge(false, false),
+            Action::RaiseMin => self.nudge(false, true),
+            Action::LowerMax => self.nudge(true, false),
+            Action::RaiseMax => self.nudge(true, true),
+            Action::FitRange => self.stale = true,
+            _ => return false,
+        }
+        true
+    }
+
+    pub fn describe(&self) -> String {
+        let center: String = match self.info.center {
+            u32::MAX => "origin".to_string(),
+            i => format!("particle {}", i),
+        };
+        format!(
+            "color by {} ({}{}), range [{:e}, {:e}], center {}",
+            MODES[self.info.mode as usize],
+            COLORMAPS[self.info.colormap as usize].0,
+            if self.info.log == 1 { ", log" } else { "" },
+            self.info.min,
+            self.info.max,
+            center
+        )
+    }
+
+    pub fn update(&self, queue: &wgpu::Queue) {
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[self.info]));
+    }
+
+    // recomputes the density estimate when coloring by it
+    pub fn density(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        buffers: &ParticleBuffers,
+    ) {
+        if self.info.mode != DENSITY {
+            return;
+        }
+        if buffers.count != self.count {
+            self.count = buffers.count;
+            self.density = create_density(device, self.count);
+            self.bind_group = create_bind_group(
+                device,
+                &self.bind_group_layout,
+                &self.buffer,
+                &self.colormaps,
+                &self.density,
+            );
+            self.density_bind_group = create_density_bind_group(
+                device,
+                &self.density_layout,
+                &self.buffer,
+                &self.density,
+            );
+        }
+        let mut cpass: wgpu::ComputePass<'_> =
+            encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Density Pass"),
+            });
+        cpass.set_pipeline(&self.density_pipeline);
+        cpass.set_bind_group(0, buffers.draw_bind_group(), &[]);
+        cpass.set_bind_group(1, &self.density_bind_group, &[]);
+        cpass.dispatch_workgroups((self.count as u32).div_ceil(64), 1, 1);
+    }
+}
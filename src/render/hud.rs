@@ -0,0 +1,311 @@
+use {crate::render::bindings::Action, wgpu::util::DeviceExt};
+
+// characters the glyph buffer holds, longer text is cut off
+const MAX_GLYPHS: usize = 4096;
+// screen pixels per font pixel
+const SCALE: f32 = 2.0;
+// font pixels above the first line and per line, as in hud.wgsl
+const MARGIN: f32 = 4.0;
+const LINE: f32 = 10.0;
+
+// 5x8 font for ascii 32 to 126, a byte per column with bit 0 at the top
+#[rustfmt::skip]
+const FONT: [[u8; 5]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00], [0x00, 0x00, 0x5F, 0x00, 0x00], [0x00, 0x07, 0x00, 0x07, 0x00],
+    [0x14, 0x7F, 0x14, 0x7F, 0x14], [0x24, 0x2A, 0x7F, 0x2A, 0x12], [0x23, 0x13, 0x08, 0x64, 0x62],
+    [0x36, 0x49, 0x56, 0x20, 0x50], [0x00, 0x08, 0x07, 0x03, 0x00], [0x00, 0x1C, 0x22, 0x41, 0x00],
+    [0x00, 0x41, 0x22, 0x1C, 0x00], [0x2A, 0x1C, 0x7F, 0x1C, 0x2A], [0x08, 0x08, 0x3E, 0x08, 0x08],
+    [0x00, 0x80, 0x70, 0x30, 0x00], [0x08, 0x08, 0x08, 0x08, 0x08], [0x00, 0x00, 0x60, 0x60, 0x00],
+    [0x20, 0x10, 0x08, 0x04, 0x02], [0x3E, 0x51, 0x49, 0x45, 0x3E], [0x00, 0x42, 0x7F, 0x40, 0x00],
+    [0x72, 0x49, 0x49, 0x49, 0x46], [0x21, 0x41, 0x49, 0x4D, 0x33], [0x18, 0x14, 0x12, 0x7F, 0x10],
+    [0x27, 0x45, 0x45, 0x45, 0x39], [0x3C, 0x4A, 0x49, 0x49, 0x31], [0x41, 0x21, 0x11, 0x09, 0x07],
+    [0x36, 0x49, 0x49, 0x49, 0x36], [0x46, 0x49, 0x49, 0x29, 0x1E], [0x00, 0x00, 0x14, 0x00, 0x00],
+    [0x00, 0x40, 0x34, 0x00, 0x00], [0x00, 0x08, 0x14, 0x22, 0x41], [0x14, 0x14, 0x14, 0x14, 0x14],
+    [0x00, 0x41, 0x22, 0x14, 0x08], [0x02, 0x01, 0x59, 0x09, 0x06], [0x3E, 0x41, 0x5D, 0x59, 0x4E],
+    [0x7C, 0x12, 0x11, 0x12, 0x7C], [0x7F, 0x49, 0x49, 0x49, 0x36], [0x3E, 0x41, 0x41, 0x41, 0x22],
+    [0x7F, 0x41, 0x41, 0x41, 0x3E], [0x7F, 0x49, 0x49, 0x49, 0x41], [0x7F, 0x09, 0x09, 0x09, 0x01],
+    [0x3E, 0x41, 0x41, 0x51, 0x73], [0x7F, 0x08, 0x08, 0x08, 0x7F], [0x00, 0x41, 0x7F, 0x41, 0x00],
+    [0x20, 0x40, 0x41, 0x3F, 0x01], [0x7F, 0x08, 0x14, 0x22, 0x41], [0x7F, 0x40, 0x40, 0x40, 0x40],
+    [0x7F, 0x02, 0x1C, 0x02, 0x7F], [0x7F, 0x04, 0x08, 0x10, 0x7F], [0x3E, 0x41, 0x41, 0x41, 0x3E],
+    [0x7F, 0x09, 0x09, 0x09, 0x06], [0x3E, 0x41, 0x51, 0x21, 0x5E], [0x7F, 0x09, 0x19, 0x29, 0x46],
+    [0x26, 0x49, 0x49, 0x49, 0x32], [0x03, 0x01, 0x7F, 0x01, 0x03], [0x3F, 0x40, 0x40, 0x40, 0x3F],
+    [0x1F, 0x20, 0x40, 0x20, 0x1F], [0x3F, 0x40, 0x38, 0x40, 0x3F], [0x63, 0x14, 0x08, 0x14, 0x63],
+    [0x03, 0x04, 0x78, 0x04, 0x03], [0x61, 0x59, 0x49, 0x4D, 0x43], [0x00, 0x7F, 0x41, 0x41, 0x41],
+    [0x02, 0x04, 0x08, 0x10, 0x20], [0x00, 0x41, 0x41, 0x41, 0x7F], [0x04, 0x02, 0x01, 0x02, 0x04],
+    [0x40, 0x40, 0x40, 0x40, 0x40], [0x00, 0x03, 0x07, 0x08, 0x00], [0x20, 0x54, 0x54, 0x78, 0x40],
+    [0x7F, 0x28, 0x44, 0x44, 0x38], [0x38, 0x44, 0x44, 0x44, 0x28], [0x38, 0x44, 0x44, 0x28, 0x7F],
+    [0x38, 0x54, 0x54, 0x54, 0x18], [0x00, 0x08, 0x7E, 0x09, 0x02], [0x18, 0xA4, 0xA4, 0x9C, 0x78],
+    [0x7F, 0x08, 0x04, 0x04, 0x78], [0x00, 0x44, 0x7D, 0x40, 0x00], [0x20, 0x40, 0x40, 0x3D, 0x00],
+    [0x7F, 0x10, 0x28, 0x44, 0x00], [0x00, 0x41, 0x7F, 0x40, 0x00], [0x7C, 0x04, 0x78, 0x04, 0x78],
+    [0x7C, 0x08, 0x04, 0x04, 0x78], [0x38, 0x44, 0x44, 0x44, 0x38], [0xFC, 0x18, 0x24, 0x24, 0x18],
+    [0x18, 0x24, 0x24, 0x18, 0xFC], [0x7C, 0x08, 0x04, 0x04, 0x08], [0x48, 0x54, 0x54, 0x54, 0x24],
+    [0x04, 0x04, 0x3F, 0x44, 0x24], [0x3C, 0x40, 0x40, 0x20, 0x7C], [0x1C, 0x20, 0x40, 0x20, 0x1C],
+    [0x3C, 0x40, 0x30, 0x40, 0x3C], [0x44, 0x28, 0x10, 0x28, 0x44], [0x4C, 0x90, 0x90, 0x90, 0x7C],
+    [0x44, 0x64, 0x54, 0x4C, 0x44], [0x00, 0x08, 0x36, 0x41, 0x00], [0x00, 0x00, 0x77, 0x00, 0x00],
+    [0x00, 0x41, 0x36, 0x08, 0x00], [0x02, 0x01, 0x02, 0x04, 0x02],
+];
+
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+struct HudInfo {
+    viewport: [f32; 2],
+    scale: f32,
+    count: u32,
+}
+
+// what the hud shows, the fields left as None are not known
+#[derive(Clone, Debug)]
+pub struct Stats {
+    pub time: f32,
+    pub step: i64,
+    // negative when running in reverse
+    pub dt: f32,
+    // steps each step of dt is split into
+    pub substeps: u32,
+    // simulated time per wall clock second
+    pub rate: Option<f32>,
+    // the force calculation and integrator
+    pub method: &'static str,
+    pub paused: bool,
+    pub particles: usize,
+    // wall clock seconds per frame
+    pub frame_time: Option<f32>,
+    pub steps_per_second: Option<f32>,
+    pub camera: [f32; 3],
+    pub energy_drift: Option<f64>,
+    // the position in a replay
+    pub timeline: Option<String>,
+    // the picked particle, see pick::inspect
+    pub inspection: Vec<String>,
+}
+
+impl Stats {
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines: Vec<String> = vec![
+            format!(
+                "t {:.4e}  step {}  dt {:.3e} x{}{}{}",
+                self.time,
+                self.step,
+                self.dt,
+                self.substeps,
+                self.rate
+                    .map(|rate| format!("  {:.3e}/s", rate))
+                    .unwrap_or_default(),
+                if self.paused { "  paused" } else { "" }
+            ),
+            format!("{} particles, {}", self.particles, self.method),
+        ];
+        if let Some(frame_time) = self.frame_time {
+            lines.push(format!(
+                "{:.1} ms/frame  {:.0} steps/s",
+                frame_time * 1000.0,
+                self.steps_per_second.unwrap_or(0.0)
+            ));
+        }
+        let [x, y, z] = self.camera;
+        lines.push(format!("camera {:.3e} {:.3e} {:.3e}", x, y, z));
+        if let Some(drift) = self.energy_drift {
+            lines.push(format!("energy drift {:.3e}", drift));
+        }
+        lines.extend(self.timeline.clone());
+        lines.extend(self.inspection.iter().cloned());
+        lines
+    }
+}
+
+// a text overlay in the top left corner, drawn onto the final target after tone
+// mapping with a bitmap font so it needs no font files
+pub struct Hud {
+    // whether the statistics are wanted, the text given to set_text is drawn either way
+    pub visible: bool,
+    info: HudInfo,
+    info_buffer: wgpu::Buffer,
+    glyph_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    pipeline: wgpu::RenderPipeline,
+}
+
+fn buffer_entry(binding: u32, ty: wgpu::BufferBindingType) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Buffer {
+            ty,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    }
+}
+
+impl Hud {
+    // `format`, `width` and `height` describe the final target
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        let info: HudInfo = HudInfo {
+            viewport: [width as f32, height as f32],
+            scale: SCALE,
+            count: 0,
+        };
+        let info_buffer: wgpu::Buffer =
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("HudInfo Buffer"),
+                contents: bytemuck::cast_slice(&[info]),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            });
+        let columns: Vec<u32> = FONT.iter().flatten().map(|&c| c as u32).collect();
+        let font_buffer: wgpu::Buffer =
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Font Buffer"),
+                contents: bytemuck::cast_slice(&columns),
+                usage: wgpu::BufferUsages::STORAGE,
+            });
+        let glyph_buffer: wgpu::Buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            size: (MAX_GLYPHS * std::mem::size_of::<u32>()) as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            label: Some("Glyph Buffer"),
+            mapped_at_creation: false,
+        });
+        let bind_group_layout: wgpu::BindGroupLayout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Hud Bind Group Layout"),
+                entries: &[
+                    buffer_entry(0, wgpu::BufferBindingType::Uniform),
+                    buffer_entry(1, wgpu::BufferBindingType::Storage { read_only: true }),
+                    buffer_entry(2, wgpu::BufferBindingType::Storage { read_only: true }),
+                ],
+            });
+        let bind_group: wgpu::BindGroup = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Hud Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: info_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: font_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: glyph_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let module: wgpu::ShaderModule =
+            device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("Hud Shader"),
+                source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/hud.wgsl").into()),
+            });
+        let layout: wgpu::PipelineLayout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Hud Pipeline Layout"),
+                bind_group_layouts: &[&bind_group_layout],
+                push_constant_ranges: &[],
+            });
+        let pipeline: wgpu::RenderPipeline =
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Hud Pipeline"),
+                layout: Some(&layout),
+                vertex: wgpu::VertexState {
+                    module: &module,
+                    entry_point: "vs_main",
+                    buffers: &[],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &module,
+                    entry_point: "fs_main",
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format,
+                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+            });
+
+        Self {
+            visible: true,
+            info,
+            info_buffer,
+            glyph_buffer,
+            bind_group,
+            pipeline,
+        }
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.info.viewport = [width as f32, height as f32];
+    }
+
+    // returns whether the action changed the hud
+    pub fn act(&mut self, action: Action) -> bool {
+        if action != Action::Hud {
+            return false;
+        }
+        self.visible = !self.visible;
+        true
+    }
+
+    pub fn describe(&self) -> &'static str {
+        if self.visible {
+            "hud on"
+        } else {
+            "hud off"
+        }
+    }
+
+    // replaces the text, one line per string from the top and `bottom` on the last
+    // lines that fit
+    pub fn set_text(&mut self, queue: &wgpu::Queue, lines: &[String], bottom: &[String]) {
+        let rows: usize =
+            ((self.info.viewport[1] / SCALE - MARGIN) / LINE - 1.0).max(0.0) as usize + 1;
+        let first: usize = rows.saturating_sub(bottom.len()).max(lines.len());
+        let glyphs: Vec<u32> = lines
+            .iter()
+            .enumerate()
+            .chain(bottom.iter().enumerate().map(|(i, text)| (first + i, text)))
+            .flat_map(|(line, text)| {
+                text.bytes().enumerate().map(move |(column, c)| {
+                    c as u32 | (column as u32 & 0xFFF) << 8 | (line as u32) << 20
+                })
+            })
+            .take(MAX_GLYPHS)
+            .collect();
+        self.info.count = glyphs.len() as u32;
+        queue.write_buffer(&self.glyph_buffer, 0, bytemuck::cast_slice(&glyphs));
+        queue.write_buffer(&self.info_buffer, 0, bytemuck::cast_slice(&[self.info]));
+    }
+
+    // draws over what is already in `target`
+    pub fn draw(&self, encoder: &mut wgpu::CommandEncoder, target: &wgpu::TextureView) {
+        if self.info.count == 0 {
+            return;
+        }
+        let mut rpass: wgpu::RenderPass<'_> =
+            encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Hud Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: target,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+        rpass.set_pipeline(&self.pipeline);
+        rpass.set_bind_group(0, &self.bind_group, &[]);
+        rpass.draw(0..6, 0..self.info.count);
+    }
+}
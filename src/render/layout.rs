@@ -0,0 +1,115 @@
+use {
+    crate::render::{bindings::Action, camera::View},
+    cgmath::{Point3, Vector3},
+};
+
+// what a viewport looks from
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Viewpoint {
+    Free,
+    FaceOn,
+    EdgeOn,
+    SideOn,
+}
+
+impl Viewpoint {
+    pub fn describe(self) -> &'static str {
+        match self {
+            Viewpoint::Free => "free",
+            Viewpoint::FaceOn => "face-on",
+            Viewpoint::EdgeOn => "edge-on",
+            Viewpoint::SideOn => "side-on",
+        }
+    }
+
+    // the fixed viewpoints look at `center` from `distance` away, face-on down the z
+    // axis onto the x y plane the galaxies start in, edge-on along y and side-on
+    // along x with z up; returns the view and its up direction
+    pub fn look(
+        self,
+        center: Point3<f32>,
+        distance: f32,
+        fovy: f32,
+    ) -> Option<(View, Vector3<f32>)> {
+        let (dir, up): (Vector3<f32>, Vector3<f32>) = match self {
+            Viewpoint::Free => return None,
+            Viewpoint::FaceOn => (-Vector3::unit_z(), Vector3::unit_y()),
+            Viewpoint::EdgeOn => (Vector3::unit_y(), Vector3::unit_z()),
+            Viewpoint::SideOn => (-Vector3::unit_x(), Vector3::unit_z()),
+        };
+        let view: View = View {
+            pos: center - dir * distance,
+            dir,
+            fovy,
+        };
+        Some((view, up))
+    }
+}
+
+// a part of the window and what it shows, `region` is x, y, width and height in
+// pixels from the top left
+#[derive(Clone, Copy, Debug)]
+pub struct Viewport {
+    pub viewpoint: Viewpoint,
+    pub region: [u32; 4],
+}
+
+// the window shows the free camera alone or, split with F5, a 2x2 grid of it and
+// the fixed viewpoints, all drawn from the same particle buffer in one frame
+pub struct Layout {
+    pub split: bool,
+}
+
+impl Layout {
+    pub fn new() -> Self {
+        Self { split: false }
+    }
+
+    // returns whether the action changed the layout
+    pub fn act(&mut self, action: Action) -> bool {
+        if action != Action::SplitView {
+            return false;
+        }
+        self.split = !self.split;
+        true
+    }
+
+    pub fn describe(&self) -> &'static str {
+        if self.split {
+            "split into free, face-on, edge-on and side-on views"
+        } else {
+            "single view"
+        }
+    }
+
+    // the size every viewport is drawn at
+    pub fn tile(&self, width: u32, height: u32) -> [u32; 2] {
+        if self.split {
+            [(width / 2).max(1), (height / 2).max(1)]
+        } else {
+            [width, height]
+        }
+    }
+
+    pub fn viewports(&self, width: u32, height: u32) -> Vec<Viewport> {
+        let [w, h] = self.tile(width, height);
+        if !self.split {
+            return vec![Viewport {
+                viewpoint: Viewpoint::Free,
+                region: [0, 0, w, h],
+            }];
+        }
+        [
+            (Viewpoint::Free, 0, 0),
+            (Viewpoint::FaceOn, w, 0),
+            (Viewpoint::EdgeOn, 0, h),
+            (Viewpoint::SideOn, w, h),
+        ]
+        .iter()
+        .map(|&(viewpoint, x, y)| Viewport {
+            viewpoint,
+            region: [x, y, w, h],
+        })
+        .collect()
+    }
+}
@@ -0,0 +1,256 @@
+use {
+    crate::{
+        buffers::ParticleBuffers,
+        build_matrix,
+        compute::{Integrator, Kernel},
+        error::Error,
+        headless::state::display::Display,
+        options::Options,
+        render::{
+            camera::{self, CameraPath, Lens, View},
+            hud::Stats,
+            scene::Scene,
+            CAMERA_POS,
+        },
+        GpuInfo, Particle, FOVY,
+    },
+    cgmath::{prelude::*, Deg, Point3, Quaternion},
+    std::{
+        fs,
+        io::{self, BufWriter},
+        path::{Path, PathBuf},
+        time::Instant,
+    },
+    wgpu::util::DeviceExt,
+};
+
+// frames are written as 8-bit sRGB
+const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+// how long an offscreen run lasts and where its frames go
+#[derive(Clone, Debug)]
+pub struct Frames {
+    pub dir: PathBuf,
+    pub steps: u64,
+    // simulation steps between frames
+    pub every: u64,
+    pub width: u32,
+    pub height: u32,
+    // degrees the camera circles the origin by per frame
+    pub orbit: f32,
+    // replaces the orbit when given
+    pub camera_path: Option<CameraPath>,
+}
+
+pub fn path(dir: &Path, frame: u64) -> PathBuf {
+    dir.join(format!("frame_{:06}.png", frame))
+}
+
+// the camera path at simulated `time`, or else the interactive start position
+// turned about y by the orbit
+fn camera(frames: &Frames, frame: u64, time: f32) -> View {
+    if let Some(view) = frames.camera_path.as_ref().and_then(|path| path.at(time)) {
+        return view;
+    }
+    let turn: Quaternion<f32> = Quaternion::from_angle_y(Deg(frames.orbit * frame as f32));
+    let pos: cgmath::Vector3<f32> = turn.rotate_vector(CAMERA_POS.into());
+    View {
+        pos: Point3::from_vec(pos),
+        dir: -pos.normalize(),
+        fovy: FOVY,
+    }
+}
+
+fn write_png(path: &Path, width: u32, height: u32, rgba: &[u8]) -> io::Result<()> {
+    let mut encoder: png::Encoder<'_, BufWriter<fs::File>> =
+        png::Encoder::new(BufWriter::new(fs::File::create(path)?), width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_source_srgb(png::SrgbRenderingIntent::Perceptual);
+    encoder.write_header()?.write_image_data(rgba)?;
+    Ok(())
+}
+
+// maps `buffer` and strips the row padding the texture copy needed
+async fn read_rgba(
+    device: &wgpu::Device,
+    buffer: &wgpu::Buffer,
+    width: u32,
+    height: u32,
+    padded_row: u32,
+) -> Result<Vec<u8>, Error> {
+    let slice: wgpu::BufferSlice<'_> = buffer.slice(..);
+    let (sender, receiver) = futures::channel::oneshot::channel();
+    slice.map_async(wgpu::MapMode::Read, move |result| {
+        let _ = sender.send(result);
+    });
+    device.poll(wgpu::Maintain::Wait);
+    receiver
+        .await
+        .map_err(|_| Error::Device("frame buffer was never mapped".into()))?
+        .map_err(|e| Error::Device(format!("failed to map frame buffer: {}", e)))?;
+    let rgba: Vec<u8> = slice
+        .get_mapped_range()
+        .chunks_exact(padded_row as usize)
+        .take(height as usize)
+        .flat_map(|row| &row[..width as usize * 4])
+        .copied()
+        .collect();
+    buffer.unmap();
+    Ok(rgba)
+}
+
+// runs the simulation without a window, rendering a png every `frames.every` steps
+pub async fn run(
+    mut gpu_info: GpuInfo,
+    particles: Vec<Particle>,
+    indexes: Vec<usize>,
+    frames: &Frames,
+    options: &Options,
+) -> Result<(), Error> {
+    let display: Display = Display::new(options).await?;
+    let (device, queue) = (&display.device, &display.queue);
+    // option parsing already turned away empty frames, the largest depends on the device
+    let largest: u32 = device.limits().max_texture_dimension_2d;
+    if frames.width > largest || frames.height > largest {
+        return Err(Error::Usage(format!(
+            "--size {}x{} is larger than the {} pixels the device allows",
+            frames.width, frames.height, largest
+        )));
+    }
+    let gpu_buffer: wgpu::Buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("GpuInfo Buffer"),
+        contents: bytemuck::cast_slice(&[gpu_info]),
+        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+    });
+    let mut buffers: ParticleBuffers =
+        ParticleBuffers::new(device, &gpu_buffer, &particles, wgpu::ShaderStages::VERTEX);
+    let kernel: Kernel = Kernel::new(
+        device,
+        &buffers.bind_group_layout,
+        options.workgroup_size,
+        Integrator::Euler,
+    );
+    let centers: Vec<u32> = indexes.iter().map(|&i| i as u32).collect();
+    let mut scene: Scene = Scene::new(
+        device,
+        queue,
+        &buffers.draw_layout,
+        &particles,
+        centers,
+        FORMAT,
+        frames.width,
+        frames.height,
+    );
+    scene.trails.set_mode(device, options.trails, particles.len());
+    scene.hud.visible = options.hud;
+    scene.projection.enabled = options.projection;
+
+    let size: wgpu::Extent3d = wgpu::Extent3d {
+        width: frames.width,
+        height: frames.height,
+        depth_or_array_layers: 1,
+    };
+    let target: wgpu::Texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Frame Texture"),
+        size,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: FORMAT,
+        view_formats: &[],
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+    });
+    let view: wgpu::TextureView = target.create_view(&wgpu::TextureViewDescriptor::default());
+    let padded_row: u32 = (frames.width * 4).next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+    let readback: wgpu::Buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        size: (padded_row * frames.height) as u64,
+        usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+        label: Some("Frame Readback Buffer"),
+        mapped_at_creation: false,
+    });
+    fs::create_dir_all(&frames.dir).map_err(|e| Error::Io(frames.dir.clone(), e))?;
+
+    let start: Instant = Instant::now();
+    let aspect: f32 = frames.width as f32 / frames.height as f32;
+    let camera_distance: f32 = cgmath::Vector3::from(CAMERA_POS).magnitude();
+    let mut lens: Lens = Lens::new(FOVY, camera::extent(&particles, camera_distance));
+    lens.orthographic = options.orthographic;
+    let mut step: u64 = 0;
+    let mut frame: u64 = 0;
+    loop {
+        let camera: View = camera(frames, frame, step as f32 * gpu_info.motion);
+        lens.fovy = camera.fovy;
+        lens.distance = camera.pos.to_vec().magnitude();
+        gpu_info.matrix = build_matrix(camera.pos, camera.dir, aspect, lens).into();
+        scene.post.set_lens(&lens);
+        scene.post.update(queue);
+        queue.write_buffer(&gpu_buffer, 0, bytemuck::cast_slice(&[gpu_info]));
+        if options.hud {
+            let stats: Stats = Stats {
+                time: step as f32 * gpu_info.motion,
+                step: step as i64,
+                dt: gpu_info.motion,
+                substeps: 1,
+                rate: None,
+                method: kernel.integrator.method(),
+                paused: false,
+                particles: particles.len(),
+                frame_time: None,
+                steps_per_second: None,
+                camera: camera.pos.into(),
+                energy_drift: None,
+                timeline: None,
+                inspection: Vec::new(),
+            };
+            scene.hud.set_text(queue, &stats.lines(), &[]);
+        }
+        let mut encoder: wgpu::CommandEncoder =
+            device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Frame Encoder"),
+            });
+        scene.draw(device, queue, &mut encoder, &buffers, &view, true);
+        encoder.copy_texture_to_buffer(
+            target.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &readback,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_row),
+                    rows_per_image: Some(frames.height),
+                },
+            },
+            size,
+        );
+        queue.submit([encoder.finish()]);
+        display.failures.check()?;
+        let rgba: Vec<u8> =
+            read_rgba(device, &readback, frames.width, frames.height, padded_row).await?;
+        let png: PathBuf = path(&frames.dir, frame);
+        write_png(&png, frames.width, frames.height, &rgba).map_err(|e| Error::Io(png, e))?;
+        frame += 1;
+
+        if step >= frames.steps {
+            break;
+        }
+        // the steps up to the next frame, in batches like a headless run
+        let until: u64 = (step + frames.every.max(1)).min(frames.steps);
+        while step < until {
+            let batch: u64 = (until - step).min(64);
+            let mut encoder: wgpu::CommandEncoder =
+                device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("Command Encoder"),
+                });
+            kernel.step(&mut encoder, &mut buffers, batch as u32);
+            queue.submit([encoder.finish()]);
+            step += batch;
+        }
+    }
+    println!(
+        "{} frames of {} steps in {:.2}s",
+        frame,
+        step,
+        start.elapsed().as_secs_f64()
+    );
+    Ok(())
+}
@@ -0,0 +1,383 @@
+use {
+    crate::{
+        buffers::ParticleBuffers,
+        render::{bindings::Action, camera::View},
+        Particle,
+    },
+    cgmath::{prelude::*, Point3, Vector3},
+    std::collections::BTreeMap,
+    wgpu::util::DeviceExt,
+};
+
+// vertices the line buffer holds
+const MAX_VERTICES: usize = 256;
+// grid lines each side of the focus
+const GRID_LINES: i32 = 10;
+// the scale bar's left end and height above the bottom, in pixels, clear of the
+// bottom line of hud text
+const BAR_LEFT: f32 = 8.0;
+const BAR_BOTTOM: f32 = 52.0;
+
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+struct LineVertex {
+    pos: [f32; 3],
+    color: [f32; 4],
+}
+
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+struct OverlayInfo {
+    center_of_mass: [f32; 3],
+    marker_pixels: f32,
+    viewport: [f32; 2],
+    count: u32,
+    _pad: f32,
+}
+
+// the largest 1, 2 or 5 times a power of ten up to x
+fn nice(x: f32) -> f32 {
+    let power: f32 = 10f32.powf(x.log10().floor());
+    let mantissa: f32 = x / power;
+    power
+        * if mantissa >= 5.0 {
+            5.0
+        } else if mantissa >= 2.0 {
+            2.0
+        } else {
+            1.0
+        }
+}
+
+// guides drawn over the finished frame: a grid in the z = 0 plane around the focus,
+// the world axes at the origin, a scale bar and crosses over the galaxy centers
+// and the center of mass; F1 to F4 toggle them
+pub struct Overlay {
+    pub grid: bool,
+    pub axes: bool,
+    pub scale: bool,
+    pub markers: bool,
+    info: OverlayInfo,
+    // world length of the scale bar and of a grid cell
+    spacing: f32,
+    vertices: u32,
+    buffer: wgpu::Buffer,
+    vertex_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    line_pipeline: wgpu::RenderPipeline,
+    marker_pipeline: wgpu::RenderPipeline,
+}
+
+impl Overlay {
+    // `format`, `width` and `height` describe the final target
+    pub fn new(
+        device: &wgpu::Device,
+        particle_layout: &wgpu::BindGroupLayout,
+        particles: &[Particle],
+        format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        // each galaxy's center is generated first of its component
+        let mut firsts: BTreeMap<u32, u32> = BTreeMap::new();
+        for (i, p) in particles.iter().enumerate() {
+            firsts.entry(p.component).or_insert(i as u32);
+        }
+        let markers: Vec<u32> = firsts.into_values().collect();
+        let info: OverlayInfo = OverlayInfo {
+            center_of_mass: [0.0; 3],
+            marker_pixels: 8.0,
+            viewport: [width as f32, height as f32],
+            count: markers.len() as u32,
+            _pad: 0.0,
+        };
+        let buffer: wgpu::Buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("OverlayInfo Buffer"),
+            contents: bytemuck::cast_slice(&[info]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let marker_buffer: wgpu::Buffer =
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Marker Buffer"),
+                contents: bytemuck::cast_slice(if markers.is_empty() { &[0] } else { &markers }),
+                usage: wgpu::BufferUsages::STORAGE,
+            });
+        let vertex_buffer: wgpu::Buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            size: (MAX_VERTICES * std::mem::size_of::<LineVertex>()) as u64,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            label: Some("Overlay Vertex Buffer"),
+            mapped_at_creation: false,
+        });
+        let bind_group_layout: wgpu::BindGroupLayout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Overlay Bind Group Layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::VERTEX,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::VERTEX,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: true },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+            });
+        let bind_group: wgpu::BindGroup = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Overlay Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: marker_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let source: &str = include_str!("../shaders/overlay.wgsl");
+        let module: wgpu::ShaderModule =
+            device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("Overlay Shader"),
+                source: wgpu::ShaderSource::Wgsl(source.into()),
+            });
+        let layout: wgpu::PipelineLayout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Overlay Pipeline Layout"),
+                bind_group_layouts: &[particle_layout, &bind_group_layout],
+                push_constant_ranges: &[],
+            });
+        let pipeline = |entry_point: &str, buffers: &[wgpu::VertexBufferLayout<'_>]| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Overlay Pipeline"),
+                layout: Some(&layout),
+                vertex: wgpu::VertexState {
+                    module: &module,
+                    entry_point,
+                    buffers,
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &module,
+                    entry_point: "fs_main",
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format,
+                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::LineList,
+                    ..Default::default()
+                },
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+            })
+        };
+        let line_pipeline: wgpu::RenderPipeline = pipeline(
+            "vs_lines",
+            &[wgpu::VertexBufferLayout {
+                array_stride: std::mem::size_of::<LineVertex>() as u64,
+                step_mode: wgpu::VertexStepMode::Vertex,
+                attributes: &wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x4],
+            }],
+        );
+        let marker_pipeline: wgpu::RenderPipeline = pipeline("vs_markers", &[]);
+
+        Self {
+            grid: false,
+            axes: false,
+            scale: false,
+            markers: false,
+            info,
+            spacing: 1.0,
+            vertices: 0,
+            buffer,
+            vertex_buffer,
+            bind_group,
+            line_pipeline,
+            marker_pipeline,
+        }
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.info.viewport = [width as f32, height as f32];
+    }
+
+    // returns whether the action changed the overlays
+    pub fn act(&mut self, action: Action) -> bool {
+        match action {
+            Action::Grid => self.grid = !self.grid,
+            Action::Axes => self.axes = !self.axes,
+            Action::ScaleBar => self.scale = !self.scale,
+            Action::Markers => self.markers = !self.markers,
+            _ => return false,
+        }
+        true
+    }
+
+    pub fn describe(&self) -> String {
+        let shown: Vec<&str> = [
+            (self.grid, "grid"),
+            (self.axes, "axes"),
+            (self.scale, "scale bar"),
+            (self.markers, "markers"),
+        ]
+        .iter()
+        .filter(|(on, _)| *on)
+        .map(|&(_, name)| name)
+        .collect();
+        if shown.is_empty() {
+            "no overlays".to_string()
+        } else {
+            format!("overlays: {}", shown.join(", "))
+        }
+    }
+
+    // the scale bar's length, for the hud to write under it
+    pub fn labels(&self) -> Vec<String> {
+        if self.scale {
+            vec![format!("{:e}", self.spacing)]
+        } else {
+            Vec::new()
+        }
+    }
+
+    // rebuilds the lines for `camera` with `up` at the top, sized for a view `focus`
+    // away
+    pub fn update(
+        &mut self,
+        queue: &wgpu::Queue,
+        camera: &View,
+        up: Vector3<f32>,
+        focus: f32,
+        center_of_mass: Point3<f32>,
+    ) {
+        let [width, height] = self.info.viewport;
+        // half the view at the focus distance
+        let half_height: f32 = focus * (camera.fovy / 2.0).tan();
+        let half_width: f32 = half_height * width / height;
+        self.spacing = nice(half_width * 0.5);
+        let l: f32 = self.spacing;
+
+        let mut lines: Vec<LineVertex> = Vec::new();
+        let mut line = |a: Point3<f32>, b: Point3<f32>, color: [f32; 4]| {
+            lines.push(LineVertex {
+                pos: a.into(),
+                color,
+            });
+            lines.push(LineVertex {
+                pos: b.into(),
+                color,
+            });
+        };
+        if self.grid {
+            let ahead: Point3<f32> = camera.pos + camera.dir * focus;
+            let (cx, cy) = ((ahead.x / l).round() * l, (ahead.y / l).round() * l);
+            let extent: f32 = GRID_LINES as f32 * l;
+            for k in -GRID_LINES..=GRID_LINES {
+                let offset: f32 = k as f32 * l;
+                let color: [f32; 4] = [0.5, 0.5, 0.5, if k == 0 { 0.5 } else { 0.25 }];
+                line(
+                    Point3::new(cx + offset, cy - extent, 0.0),
+                    Point3::new(cx + offset, cy + extent, 0.0),
+                    color,
+                );
+                line(
+                    Point3::new(cx - extent, cy + offset, 0.0),
+                    Point3::new(cx + extent, cy + offset, 0.0),
+                    color,
+                );
+            }
+        }
+        if self.axes {
+            let length: f32 = GRID_LINES as f32 * l;
+            for (axis, color) in [
+                (Vector3::unit_x(), [1.0, 0.3, 0.3, 0.9]),
+                (Vector3::unit_y(), [0.3, 1.0, 0.3, 0.9]),
+                (Vector3::unit_z(), [0.3, 0.5, 1.0, 0.9]),
+            ] {
+                line(Point3::origin(), Point3::origin() + axis * length, color);
+            }
+        }
+        if self.scale {
+            // a bar of `l` across the view at the focus distance
+            let right: Vector3<f32> = camera.dir.cross(up).normalize();
+            let up: Vector3<f32> = right.cross(camera.dir);
+            let at = |x: f32, y: f32| -> Point3<f32> {
+                let ndc_x: f32 = x / width * 2.0 - 1.0;
+                let ndc_y: f32 = 1.0 - y / height * 2.0;
+                camera.pos
+                    + camera.dir * focus
+                    + right * ndc_x * half_width
+                    + up * ndc_y * half_height
+            };
+            let start: Point3<f32> = at(BAR_LEFT, height - BAR_BOTTOM);
+            let end: Point3<f32> = start + right * l;
+            let tick: Vector3<f32> = up * (6.0 / height * 2.0 * half_height);
+            let white: [f32; 4] = [1.0, 1.0, 1.0, 0.9];
+            line(start, end, white);
+            line(start, start + tick, white);
+            line(end, end + tick, white);
+        }
+        lines.truncate(MAX_VERTICES);
+        self.vertices = lines.len() as u32;
+        self.info.center_of_mass = center_of_mass.into();
+        queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&lines));
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[self.info]));
+    }
+
+    // draws over what is already in `region` of `target`, x, y, width and height in
+    // pixels
+    pub fn draw(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        buffers: &ParticleBuffers,
+        target: &wgpu::TextureView,
+        region: [u32; 4],
+    ) {
+        if self.vertices == 0 && !self.markers {
+            return;
+        }
+        let mut rpass: wgpu::RenderPass<'_> =
+            encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Overlay Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: target,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+        let [x, y, width, height] = region;
+        rpass.set_viewport(x as f32, y as f32, width as f32, height as f32, 0.0, 1.0);
+        rpass.set_bind_group(0, buffers.draw_bind_group(), &[]);
+        rpass.set_bind_group(1, &self.bind_group, &[]);
+        if self.vertices > 0 {
+            rpass.set_pipeline(&self.line_pipeline);
+            rpass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+            rpass.draw(0..self.vertices, 0..1);
+        }
+        if self.markers {
+            rpass.set_pipeline(&self.marker_pipeline);
+            rpass.draw(0..4, 0..self.info.count + 1);
+        }
+    }
+}
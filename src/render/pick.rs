@@ -0,0 +1,101 @@
+use {
+    crate::{Particle, G},
+    cgmath::{prelude::*, Matrix4, Vector3, Vector4},
+    std::f64::consts::{FRAC_PI_2, PI},
+};
+
+// how far from the cursor a click still picks a particle
+const PICK_PIXELS: f32 = 12.0;
+
+// the particle drawn nearest to `cursor`, projected with `matrix` like vs_main
+// does; particles equally near go to the one in front
+pub fn nearest(
+    particles: &[Particle],
+    matrix: Matrix4<f32>,
+    viewport: [f32; 2],
+    cursor: [f32; 2],
+) -> Option<usize> {
+    let mut best: Option<(f32, f32, usize)> = None;
+    for (i, p) in particles.iter().enumerate() {
+        let clip: Vector4<f32> = matrix * Vector3::from(p.pos).extend(1.0);
+        if clip.w <= 0.0 {
+            continue;
+        }
+        // pixels from the top left, like the cursor
+        let x: f32 = (clip.x / clip.w + 1.0) * 0.5 * viewport[0];
+        let y: f32 = (1.0 - clip.y / clip.w) * 0.5 * viewport[1];
+        let distance: f32 = (x - cursor[0]).hypot(y - cursor[1]);
+        if distance > PICK_PIXELS {
+            continue;
+        }
+        if best.is_none_or(|(d, w, _)| (distance, clip.w) < (d, w)) {
+            best = Some((distance, clip.w, i));
+        }
+    }
+    best.map(|(_, _, i)| i)
+}
+
+// what the particle at `index` orbits: the heaviest other particle of its
+// component, or of all the particles when it is alone or the heaviest itself
+pub fn host(particles: &[Particle], index: usize) -> Option<usize> {
+    let heaviest = |same_component: bool| -> Option<usize> {
+        particles
+            .iter()
+            .enumerate()
+            .filter(|&(i, p)| {
+                i != index && (!same_component || p.component == particles[index].component)
+            })
+            .max_by(|(_, a), (_, b)| a.mass.total_cmp(&b.mass))
+            .map(|(i, _)| i)
+    };
+    heaviest(true)
+        .filter(|&i| particles[i].mass >= particles[index].mass)
+        .or_else(|| heaviest(false))
+}
+
+fn to_f64(v: [f32; 3]) -> Vector3<f64> {
+    Vector3::new(v[0] as f64, v[1] as f64, v[2] as f64)
+}
+
+// the particle's properties and its orbit around `host` alone, under the softened
+// force of compute.wgsl, G m / (r^2 + calibrate), which has no kepler ellipses
+pub fn inspect(particle: &Particle, host: Option<&Particle>) -> Vec<String> {
+    let [x, y, z] = particle.pos;
+    let [vx, vy, vz] = particle.vel;
+    let mut lines: Vec<String> = vec![
+        format!(
+            "particle {}  component {}  mass {:.3e}",
+            particle.id, particle.component, particle.mass
+        ),
+        format!("pos {:.3e} {:.3e} {:.3e}", x, y, z),
+        format!("vel {:.3e} {:.3e} {:.3e}", vx, vy, vz),
+    ];
+    let Some(host) = host else {
+        return lines;
+    };
+    let r: f64 = (to_f64(particle.pos) - to_f64(host.pos)).magnitude();
+    let v: f64 = (to_f64(particle.vel) - to_f64(host.vel)).magnitude();
+    let gm: f64 = G as f64 * host.mass as f64;
+    let c: f64 = (host.calibrate as f64).sqrt();
+    if gm <= 0.0 || c == 0.0 || r == 0.0 {
+        return lines;
+    }
+    // the speed and period of a circular orbit at r, and the specific energy with
+    // the potential that vanishes at infinity as in Diagnostics
+    let circular: f64 = (gm * r / (r * r + c * c)).sqrt();
+    let energy: f64 = 0.5 * v * v - gm / c * (FRAC_PI_2 - (r / c).atan());
+    lines.push(format!(
+        "around {}: r {:.3e}  v {:.3e}  v/v_circ {:.3}",
+        host.id,
+        r,
+        v,
+        v / circular
+    ));
+    lines.push(format!(
+        "{}, energy {:.3e}  circular period {:.3e}",
+        if energy < 0.0 { "bound" } else { "unbound" },
+        energy,
+        2.0 * PI * r / circular
+    ));
+    lines
+}
@@ -0,0 +1,428 @@
+use {
+    crate::{
+        render::{bindings::Action, camera::Lens},
+        FOVY,
+    },
+    wgpu::util::DeviceExt,
+};
+
+// the particles are summed into this before tone mapping
+pub const HDR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+// bloom is blurred over this many successively halved textures
+const BLOOM_LEVELS: usize = 5;
+
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+pub struct PostInfo {
+    pub viewport: [f32; 2],
+    // projection scale, Lens::scale
+    pub scale: f32,
+    // world radius of a sprite
+    pub size: f32,
+    // sprites are never drawn smaller than this many pixels
+    pub min_pixels: f32,
+    pub brightness: f32,
+    pub exposure: f32,
+    // strength of the bloom added before tone mapping, 0 when it is off
+    pub bloom: f32,
+    // brightness above which pixels bloom, softened over `knee`
+    pub threshold: f32,
+    pub knee: f32,
+    // index of the particle drawn highlighted, u32::MAX for none
+    pub picked: u32,
+    _pad: f32,
+}
+
+// the hdr target the sprites are drawn into, its bloom chain and the tone mapping
+// onto the final target; the info bind group is also group 2 of the draw shader
+pub struct Post {
+    pub info: PostInfo,
+    pub bloom: bool,
+    pub buffer: wgpu::Buffer,
+    pub info_layout: wgpu::BindGroupLayout,
+    pub info_bind_group: wgpu::BindGroup,
+    texture_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    pub targets: Targets,
+    prefilter_pipeline: wgpu::RenderPipeline,
+    down_pipeline: wgpu::RenderPipeline,
+    up_pipeline: wgpu::RenderPipeline,
+    tonemap_pipeline: wgpu::RenderPipeline,
+}
+
+// everything that depends on the target size
+pub struct Targets {
+    pub hdr_view: wgpu::TextureView,
+    levels: Vec<wgpu::TextureView>,
+    // down[k] samples the hdr target or level k - 1 into level k
+    down: Vec<wgpu::BindGroup>,
+    // up[k] samples level k + 1 onto level k
+    up: Vec<wgpu::BindGroup>,
+    tonemap: wgpu::BindGroup,
+}
+
+fn create_target(device: &wgpu::Device, width: u32, height: u32) -> wgpu::TextureView {
+    device
+        .create_texture(&wgpu::TextureDescriptor {
+            label: Some("Hdr Texture"),
+            size: wgpu::Extent3d {
+                width: width.max(1),
+                height: height.max(1),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: HDR_FORMAT,
+            view_formats: &[],
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        })
+        .create_view(&wgpu::TextureViewDescriptor::default())
+}
+
+fn texture_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    sampler: &wgpu::Sampler,
+    source: &wgpu::TextureView,
+    glow: &wgpu::TextureView,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("Post Texture Bind Group"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(source),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(sampler),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: wgpu::BindingResource::TextureView(glow),
+            },
+        ],
+    })
+}
+
+fn create_targets(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    sampler: &wgpu::Sampler,
+    width: u32,
+    height: u32,
+) -> Targets {
+    let hdr_view: wgpu::TextureView = create_target(device, width, height);
+    let levels: Vec<wgpu::TextureView> = (1..=BLOOM_LEVELS)
+        .map(|k| create_target(device, width >> k, height >> k))
+        .collect();
+    let down: Vec<wgpu::BindGroup> = (0..BLOOM_LEVELS)
+        .map(|k| {
+            let source: &wgpu::TextureView = if k == 0 { &hdr_view } else { &levels[k - 1] };
+            texture_bind_group(device, layout, sampler, source, source)
+        })
+        .collect();
+    let up: Vec<wgpu::BindGroup> = (0..BLOOM_LEVELS - 1)
+        .map(|k| texture_bind_group(device, layout, sampler, &levels[k + 1], &levels[k + 1]))
+        .collect();
+    let tonemap: wgpu::BindGroup = texture_bind_group(device, layout, sampler, &hdr_view, &levels[0]);
+    Targets {
+        hdr_view,
+        levels,
+        down,
+        up,
+        tonemap,
+    }
+}
+
+fn create_pipeline(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    module: &wgpu::ShaderModule,
+    entry_point: &str,
+    format: wgpu::TextureFormat,
+    blend: wgpu::BlendState,
+) -> wgpu::RenderPipeline {
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some(entry_point),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module,
+            entry_point: "vs_fullscreen",
+            buffers: &[],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module,
+            entry_point,
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend: Some(blend),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+    })
+}
+
+// summing blend for the sprites and the bloom upsampling
+pub const ADDITIVE: wgpu::BlendState = wgpu::BlendState {
+    color: wgpu::BlendComponent {
+        src_factor: wgpu::BlendFactor::One,
+        dst_factor: wgpu::BlendFactor::One,
+        operation: wgpu::BlendOperation::Add,
+    },
+    alpha: wgpu::BlendComponent {
+        src_factor: wgpu::BlendFactor::One,
+        dst_factor: wgpu::BlendFactor::One,
+        operation: wgpu::BlendOperation::Add,
+    },
+};
+
+impl Post {
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat, width: u32, height: u32) -> Self {
+        let info: PostInfo = PostInfo {
+            viewport: [width as f32, height as f32],
+            scale: 1.0 / (FOVY / 2.0).tan(),
+            size: 1e-11,
+            min_pixels: 1.5,
+            brightness: 1.0,
+            exposure: 1.0,
+            bloom: 0.05,
+            threshold: 1.0,
+            knee: 0.5,
+            picked: u32::MAX,
+            _pad: 0.0,
+        };
+        let buffer: wgpu::Buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("PostInfo Buffer"),
+            contents: bytemuck::cast_slice(&[info]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let info_layout: wgpu::BindGroupLayout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Post Info Bind Group Layout"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: wgpu::BufferSize::new(
+                            std::mem::size_of::<PostInfo>() as _,
+                        ),
+                    },
+                    count: None,
+                }],
+            });
+        let info_bind_group: wgpu::BindGroup =
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Post Info Bind Group"),
+                layout: &info_layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                }],
+            });
+        let texture_entry = |binding: u32| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let texture_layout: wgpu::BindGroupLayout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Post Texture Bind Group Layout"),
+                entries: &[
+                    texture_entry(0),
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                    texture_entry(2),
+                ],
+            });
+        let sampler: wgpu::Sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Post Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let module: wgpu::ShaderModule =
+            device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("Post Shader"),
+                source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/post.wgsl").into()),
+            });
+        let pipeline_layout: wgpu::PipelineLayout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Post Pipeline Layout"),
+                bind_group_layouts: &[&info_layout, &texture_layout],
+                push_constant_ranges: &[],
+            });
+        let replace: wgpu::BlendState = wgpu::BlendState::REPLACE;
+        let prefilter_pipeline: wgpu::RenderPipeline = create_pipeline(
+            device,
+            &pipeline_layout,
+            &module,
+            "fs_prefilter",
+            HDR_FORMAT,
+            replace,
+        );
+        let down_pipeline: wgpu::RenderPipeline =
+            create_pipeline(device, &pipeline_layout, &module, "fs_down", HDR_FORMAT, replace);
+        let up_pipeline: wgpu::RenderPipeline =
+            create_pipeline(device, &pipeline_layout, &module, "fs_up", HDR_FORMAT, ADDITIVE);
+        let tonemap_pipeline: wgpu::RenderPipeline =
+            create_pipeline(device, &pipeline_layout, &module, "fs_tonemap", format, replace);
+
+        let targets: Targets = create_targets(device, &texture_layout, &sampler, width, height);
+        Self {
+            info,
+            bloom: true,
+            buffer,
+            info_layout,
+            info_bind_group,
+            texture_layout,
+            sampler,
+            targets,
+            prefilter_pipeline,
+            down_pipeline,
+            up_pipeline,
+            tonemap_pipeline,
+        }
+    }
+
+    // keeps the sprite sizes in step with the camera's projection
+    pub fn set_lens(&mut self, lens: &Lens) {
+        self.info.scale = lens.scale();
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        self.info.viewport = [width as f32, height as f32];
+        self.targets = create_targets(device, &self.texture_layout, &self.sampler, width, height);
+    }
+
+    // returns whether the action changed the post processing
+    pub fn act(&mut self, action: Action) -> bool {
+        match action {
+            Action::Bloom => self.bloom = !self.bloom,
+            Action::LessExposure => self.info.exposure /= 1.25,
+            Action::MoreExposure => self.info.exposure *= 1.25,
+            _ => return false,
+        }
+        true
+    }
+
+    pub fn describe(&self) -> String {
+        format!(
+            "exposure {:.3}, bloom {}",
+            self.info.exposure,
+            if self.bloom { "on" } else { "off" }
+        )
+    }
+
+    pub fn update(&self, queue: &wgpu::Queue) {
+        let mut info: PostInfo = self.info;
+        if !self.bloom {
+            info.bloom = 0.0;
+        }
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[info]));
+    }
+
+    // the attachment the sprites are drawn into, cleared to the background
+    pub fn scene_attachment(&self) -> wgpu::RenderPassColorAttachment<'_> {
+        wgpu::RenderPassColorAttachment {
+            view: &self.targets.hdr_view,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Clear(wgpu::Color {
+                    r: 0.002,
+                    g: 0.002,
+                    b: 0.002,
+                    a: 1.0,
+                }),
+                store: true,
+            },
+        }
+    }
+
+    fn pass(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        pipeline: &wgpu::RenderPipeline,
+        bind_group: &wgpu::BindGroup,
+        target: &wgpu::TextureView,
+        load: wgpu::LoadOp<wgpu::Color>,
+        region: Option<[u32; 4]>,
+    ) {
+        let mut rpass: wgpu::RenderPass<'_> = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Post Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations { load, store: true },
+            })],
+            depth_stencil_attachment: None,
+        });
+        if let Some([x, y, width, height]) = region {
+            rpass.set_viewport(x as f32, y as f32, width as f32, height as f32, 0.0, 1.0);
+        }
+        rpass.set_pipeline(pipeline);
+        rpass.set_bind_group(0, &self.info_bind_group, &[]);
+        rpass.set_bind_group(1, bind_group, &[]);
+        rpass.draw(0..3, 0..1);
+    }
+
+    // blooms the hdr target and tone maps it onto `region` of `target`, x, y, width
+    // and height in pixels, leaving the rest of it to other viewports
+    pub fn finish(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &wgpu::TextureView,
+        region: [u32; 4],
+    ) {
+        let targets: &Targets = &self.targets;
+        let clear: wgpu::LoadOp<wgpu::Color> = wgpu::LoadOp::Clear(wgpu::Color::BLACK);
+        if self.bloom {
+            for k in 0..BLOOM_LEVELS {
+                let pipeline: &wgpu::RenderPipeline = if k == 0 {
+                    &self.prefilter_pipeline
+                } else {
+                    &self.down_pipeline
+                };
+                self.pass(encoder, pipeline, &targets.down[k], &targets.levels[k], clear, None);
+            }
+            for k in (0..BLOOM_LEVELS - 1).rev() {
+                self.pass(
+                    encoder,
+                    &self.up_pipeline,
+                    &targets.up[k],
+                    &targets.levels[k],
+                    wgpu::LoadOp::Load,
+                    None,
+                );
+            }
+        }
+        self.pass(
+            encoder,
+            &self.tonemap_pipeline,
+            &targets.tonemap,
+            target,
+            wgpu::LoadOp::Load,
+            Some(region),
+        );
+    }
+}
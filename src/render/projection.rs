@@ -0,0 +1,291 @@
+use {
+    crate::{buffers::ParticleBuffers, render::bindings::Action, Particle},
+    wgpu::util::DeviceExt,
+};
+
+// pixels per grid cell side
+const CELL: f32 = 2.0;
+// powers of ten below the brightest cell that are colored at first, the galaxy
+// centers outweigh single stars by several
+const DECADES: f32 = 6.0;
+
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+pub struct ProjectionInfo {
+    pub grid: [u32; 2],
+    pub cell: f32,
+    // mass of one count in the grid
+    pub unit: f32,
+    pub decades: f32,
+    // index into COLORMAPS, kept in step with the particle coloring
+    pub colormap: u32,
+    // top left pixel of the viewport drawn into
+    pub origin: [f32; 2],
+}
+
+// an alternative to the sprites: the particles' mass deposited into a screen space
+// grid by compute passes and shown as a log scaled surface density
+pub struct Projection {
+    pub enabled: bool,
+    pub info: ProjectionInfo,
+    buffer: wgpu::Buffer,
+    grid: wgpu::Buffer,
+    peak: wgpu::Buffer,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    clear_pipeline: wgpu::ComputePipeline,
+    deposit_pipeline: wgpu::ComputePipeline,
+    peak_pipeline: wgpu::ComputePipeline,
+    draw_pipeline: wgpu::RenderPipeline,
+}
+
+fn entry(binding: u32, ty: wgpu::BufferBindingType) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::COMPUTE | wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Buffer {
+            ty,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    }
+}
+
+fn grid_size(width: u32, height: u32) -> [u32; 2] {
+    [
+        (width as f32 / CELL).ceil().max(1.0) as u32,
+        (height as f32 / CELL).ceil().max(1.0) as u32,
+    ]
+}
+
+fn create_grid(device: &wgpu::Device, grid: [u32; 2]) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        size: (grid[0] * grid[1]) as u64 * std::mem::size_of::<u32>() as u64,
+        usage: wgpu::BufferUsages::STORAGE,
+        label: Some("Projection Grid Buffer"),
+        mapped_at_creation: false,
+    })
+}
+
+fn create_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    buffers: [&wgpu::Buffer; 4],
+) -> wgpu::BindGroup {
+    let entries: Vec<wgpu::BindGroupEntry<'_>> = buffers
+        .iter()
+        .enumerate()
+        .map(|(binding, buffer)| wgpu::BindGroupEntry {
+            binding: binding as u32,
+            resource: buffer.as_entire_binding(),
+        })
+        .collect();
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("Projection Bind Group"),
+        layout,
+        entries: &entries,
+    })
+}
+
+impl Projection {
+    // `colormaps` is Coloring::colormaps, `format` and the size describe the final target
+    pub fn new(
+        device: &wgpu::Device,
+        particle_layout: &wgpu::BindGroupLayout,
+        particles: &[Particle],
+        colormaps: &wgpu::Buffer,
+        format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        // all the mass fits in half a u32 of counts, so no cell can overflow however
+        // much of it lands there, the other half is room for float error and each of
+        // the four shares rounding up
+        let total: f64 = particles.iter().map(|p| p.mass.max(0.0) as f64).sum();
+        let counts: f64 = (u32::MAX / 2) as f64 - 2.0 * particles.len() as f64;
+        let info: ProjectionInfo = ProjectionInfo {
+            grid: grid_size(width, height),
+            cell: CELL,
+            unit: if total > 0.0 && counts >= 1.0 {
+                (total / counts) as f32
+            } else {
+                1.0
+            },
+            decades: DECADES,
+            colormap: 0,
+            origin: [0.0; 2],
+        };
+        let buffer: wgpu::Buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("ProjectionInfo Buffer"),
+            contents: bytemuck::cast_slice(&[info]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let grid: wgpu::Buffer = create_grid(device, info.grid);
+        let peak: wgpu::Buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            size: std::mem::size_of::<u32>() as u64,
+            usage: wgpu::BufferUsages::STORAGE,
+            label: Some("Projection Peak Buffer"),
+            mapped_at_creation: false,
+        });
+        let bind_group_layout: wgpu::BindGroupLayout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Projection Bind Group Layout"),
+                entries: &[
+                    entry(0, wgpu::BufferBindingType::Uniform),
+                    entry(1, wgpu::BufferBindingType::Storage { read_only: false }),
+                    entry(2, wgpu::BufferBindingType::Storage { read_only: false }),
+                    entry(3, wgpu::BufferBindingType::Storage { read_only: true }),
+                ],
+            });
+        let bind_group: wgpu::BindGroup =
+            create_bind_group(device, &bind_group_layout, [&buffer, &grid, &peak, colormaps]);
+
+        let source: &str = include_str!("../shaders/projection.wgsl");
+        let module: wgpu::ShaderModule =
+            device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("Projection Shader"),
+                source: wgpu::ShaderSource::Wgsl(source.into()),
+            });
+        let layout: wgpu::PipelineLayout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Projection Pipeline Layout"),
+                bind_group_layouts: &[particle_layout, &bind_group_layout],
+                push_constant_ranges: &[],
+            });
+        let compute = |entry_point: &str| -> wgpu::ComputePipeline {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some("Projection Compute Pipeline"),
+                module: &module,
+                entry_point,
+                layout: Some(&layout),
+            })
+        };
+        let clear_pipeline: wgpu::ComputePipeline = compute("clear");
+        let deposit_pipeline: wgpu::ComputePipeline = compute("deposit");
+        let peak_pipeline: wgpu::ComputePipeline = compute("find_peak");
+        let draw_pipeline: wgpu::RenderPipeline =
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Projection Draw Pipeline"),
+                layout: Some(&layout),
+                vertex: wgpu::VertexState {
+                    module: &module,
+                    entry_point: "vs_fullscreen",
+                    buffers: &[],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &module,
+                    entry_point: "fs_main",
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format,
+                        blend: None,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+            });
+
+        Self {
+            enabled: false,
+            info,
+            buffer,
+            grid,
+            peak,
+            bind_group_layout,
+            bind_group,
+            clear_pipeline,
+            deposit_pipeline,
+            peak_pipeline,
+            draw_pipeline,
+        }
+    }
+
+    pub fn resize(
+        &mut self,
+        device: &wgpu::Device,
+        colormaps: &wgpu::Buffer,
+        width: u32,
+        height: u32,
+    ) {
+        self.info.grid = grid_size(width, height);
+        self.grid = create_grid(device, self.info.grid);
+        self.bind_group = create_bind_group(
+            device,
+            &self.bind_group_layout,
+            [&self.buffer, &self.grid, &self.peak, colormaps],
+        );
+    }
+
+    // returns whether the action changed the projection
+    pub fn act(&mut self, action: Action) -> bool {
+        match action {
+            Action::Projection => self.enabled = !self.enabled,
+            Action::FewerDecades if self.enabled => {
+                self.info.decades = (self.info.decades - 1.0).max(1.0)
+            }
+            Action::MoreDecades if self.enabled => self.info.decades += 1.0,
+            _ => return false,
+        }
+        true
+    }
+
+    pub fn describe(&self) -> String {
+        if self.enabled {
+            format!("projected density over {} decades", self.info.decades)
+        } else {
+            "particles".to_string()
+        }
+    }
+
+    // deposits the particles and draws the density over `region` of `target`, x, y,
+    // width and height in pixels
+    pub fn draw(
+        &mut self,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        buffers: &ParticleBuffers,
+        target: &wgpu::TextureView,
+        region: [u32; 4],
+        colormap: u32,
+    ) {
+        self.info.colormap = colormap;
+        self.info.origin = [region[0] as f32, region[1] as f32];
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[self.info]));
+        let cells: u32 = self.info.grid[0] * self.info.grid[1];
+        {
+            let mut cpass: wgpu::ComputePass<'_> =
+                encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                    label: Some("Projection Pass"),
+                });
+            cpass.set_bind_group(0, buffers.draw_bind_group(), &[]);
+            cpass.set_bind_group(1, &self.bind_group, &[]);
+            cpass.set_pipeline(&self.clear_pipeline);
+            cpass.dispatch_workgroups(cells.div_ceil(64), 1, 1);
+            cpass.set_pipeline(&self.deposit_pipeline);
+            cpass.dispatch_workgroups((buffers.count as u32).div_ceil(64), 1, 1);
+            cpass.set_pipeline(&self.peak_pipeline);
+            cpass.dispatch_workgroups(cells.div_ceil(64), 1, 1);
+        }
+        let mut rpass: wgpu::RenderPass<'_> = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Projection Draw Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+        let [x, y, width, height] = region;
+        rpass.set_viewport(x as f32, y as f32, width as f32, height as f32, 0.0, 1.0);
+        rpass.set_pipeline(&self.draw_pipeline);
+        rpass.set_bind_group(0, buffers.draw_bind_group(), &[]);
+        rpass.set_bind_group(1, &self.bind_group, &[]);
+        rpass.draw(0..3, 0..1);
+    }
+}
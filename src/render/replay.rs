@@ -0,0 +1,199 @@
+use {
+    crate::{
+        render::bindings::Action,
+        snapshot::{self, Snapshot},
+        Particle,
+    },
+    std::{
+        fs, io,
+        path::{Path, PathBuf},
+    },
+};
+
+// characters between the brackets of the hud timeline
+const TIMELINE_WIDTH: usize = 40;
+
+// the snapshots of a headless run shown in the window in place of the simulation;
+// the position is in snapshots and fractional positions are interpolated
+pub struct Replay {
+    paths: Vec<PathBuf>,
+    pub position: f32,
+    // snapshots per second, negative when playing backward
+    pub rate: f32,
+    pub interpolate: bool,
+    // at most the two snapshots around the position, read on demand
+    cache: Vec<(usize, Snapshot)>,
+    // particles in the first snapshot read; the buffers, trails, markers and tracked
+    // particles are all sized by it, so a snapshot with another count is an error
+    count: Option<usize>,
+    // what was last returned by frame
+    shown: Option<(f32, bool)>,
+}
+
+fn lerp(a: f32, b: f32, s: f32) -> f32 {
+    a + (b - a) * s
+}
+
+impl Replay {
+    // the snapshot files of `dir` in step order
+    pub fn load(dir: &Path) -> io::Result<Self> {
+        let mut paths: Vec<PathBuf> = fs::read_dir(dir)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<io::Result<_>>()?;
+        paths.retain(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with("snapshot_") && name.ends_with(".bin"))
+        });
+        // the step in the name is zero padded
+        paths.sort();
+        if paths.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no snapshots in {}", dir.display()),
+            ));
+        }
+        Ok(Self {
+            paths,
+            position: 0.0,
+            rate: 2.0,
+            interpolate: true,
+            cache: Vec::new(),
+            count: None,
+            shown: None,
+        })
+    }
+
+    fn len(&self) -> usize {
+        self.paths.len()
+    }
+
+    fn last(&self) -> f32 {
+        (self.len() - 1) as f32
+    }
+
+    // moves the position by `seconds` of playback, stopping at either end
+    pub fn advance(&mut self, seconds: f32) {
+        self.position = (self.position + self.rate * seconds).clamp(0.0, self.last());
+    }
+
+    // returns whether the action changed the playback, the time controls step
+    // through the snapshots instead
+    pub fn act(&mut self, action: Action) -> bool {
+        match action {
+            Action::FewerSteps => self.position = (self.position.ceil() - 1.0).max(0.0),
+            Action::MoreSteps => self.position = (self.position.floor() + 1.0).min(self.last()),
+            Action::Restart => self.position = 0.0,
+            Action::LastSnapshot => self.position = self.last(),
+            Action::Faster => self.rate *= 2.0,
+            Action::Slower => self.rate *= 0.5,
+            Action::Reverse => self.rate = -self.rate,
+            Action::Interpolate => self.interpolate = !self.interpolate,
+            _ => return false,
+        }
+        true
+    }
+
+    pub fn describe(&self) -> String {
+        format!(
+            "snapshot {:.2} of {}, {} snapshots/s{}",
+            self.position + 1.0,
+            self.len(),
+            self.rate,
+            if self.interpolate { ", interpolated" } else { "" }
+        )
+    }
+
+    // a text bar of the position, for the hud
+    pub fn timeline(&self) -> String {
+        let filled: usize = if self.len() > 1 {
+            (self.position / self.last() * TIMELINE_WIDTH as f32).round() as usize
+        } else {
+            TIMELINE_WIDTH
+        };
+        format!(
+            "[{}{}] {}/{}",
+            "=".repeat(filled),
+            "-".repeat(TIMELINE_WIDTH - filled),
+            self.position.floor() as usize + 1,
+            self.len()
+        )
+    }
+
+    // reads the snapshots at `indexes` that aren't cached, dropping the rest
+    fn fetch(&mut self, indexes: &[usize]) -> io::Result<()> {
+        self.cache.retain(|(i, _)| indexes.contains(i));
+        for &index in indexes {
+            if !self.cache.iter().any(|(i, _)| *i == index) {
+                let snapshot: Snapshot = snapshot::read(&self.paths[index])?;
+                let count: usize = *self.count.get_or_insert(snapshot.particles.len());
+                if snapshot.particles.len() != count {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!(
+                            "{} has {} particles, the replay started with {}",
+                            self.paths[index].display(),
+                            snapshot.particles.len(),
+                            count
+                        ),
+                    ));
+                }
+                self.cache.push((index, snapshot));
+            }
+        }
+        Ok(())
+    }
+
+    fn cached(&self, index: usize) -> &Snapshot {
+        &self.cache.iter().find(|(i, _)| *i == index).unwrap().1
+    }
+
+    // the snapshot a freshly loaded replay starts from
+    pub fn first(&mut self) -> io::Result<Snapshot> {
+        self.shown = None;
+        self.frame()?
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no snapshot to start from"))
+    }
+
+    // the particles at the position, None when that is what was last returned
+    pub fn frame(&mut self) -> io::Result<Option<Snapshot>> {
+        if self.shown == Some((self.position, self.interpolate)) {
+            return Ok(None);
+        }
+        self.shown = Some((self.position, self.interpolate));
+        let index: usize = self.position.floor() as usize;
+        let s: f32 = self.position.fract();
+        if !self.interpolate || s == 0.0 || index + 1 >= self.len() {
+            self.fetch(&[index])?;
+            return Ok(Some(self.cached(index).clone()));
+        }
+        self.fetch(&[index, index + 1])?;
+        let (a, b) = (self.cached(index), self.cached(index + 1));
+        // a cubic hermite curve through both positions, tangent to the velocities
+        let dt: f32 = (b.time - a.time) as f32;
+        let (s2, s3) = (s * s, s * s * s);
+        let (h00, h10, h01, h11) = (
+            2.0 * s3 - 3.0 * s2 + 1.0,
+            s3 - 2.0 * s2 + s,
+            -2.0 * s3 + 3.0 * s2,
+            s3 - s2,
+        );
+        let particles: Vec<Particle> = a
+            .particles
+            .iter()
+            .zip(&b.particles)
+            .map(|(pa, pb)| Particle {
+                pos: std::array::from_fn(|k| {
+                    h00 * pa.pos[k] + h10 * dt * pa.vel[k] + h01 * pb.pos[k] + h11 * dt * pb.vel[k]
+                }),
+                vel: std::array::from_fn(|k| lerp(pa.vel[k], pb.vel[k], s)),
+                ..*pa
+            })
+            .collect();
+        Ok(Some(Snapshot {
+            step: lerp(a.step as f32, b.step as f32, s).round() as u64,
+            time: a.time + (b.time - a.time) * s as f64,
+            particles,
+        }))
+    }
+}
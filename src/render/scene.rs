@@ -0,0 +1,219 @@
+use crate::{
+    buffers::ParticleBuffers,
+    render::{
+        color::Coloring,
+        hud::Hud,
+        overlay::Overlay,
+        post::{self, Post},
+        projection::Projection,
+        trail::Trails,
+    },
+    Particle,
+};
+
+// everything drawn for a frame, independent of whether it ends up in a window or a file
+pub struct Scene {
+    pub render_pipeline: wgpu::RenderPipeline,
+    pub coloring: Coloring,
+    pub post: Post,
+    pub trails: Trails,
+    pub projection: Projection,
+    pub overlay: Overlay,
+    pub hud: Hud,
+}
+
+impl Scene {
+    // `format`, `width` and `height` describe the final target
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        particle_layout: &wgpu::BindGroupLayout,
+        particles: &[Particle],
+        centers: Vec<u32>,
+        format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        let draw_source: &str = include_str!("../shaders/draw.wgsl");
+        let vs_mod: wgpu::ShaderModule =
+            device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("Vertex Shader"),
+                source: wgpu::ShaderSource::Wgsl(draw_source.into()),
+            });
+        let fs_mod: wgpu::ShaderModule =
+            device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("Fragment Shader"),
+                source: wgpu::ShaderSource::Wgsl(draw_source.into()),
+            });
+
+        let coloring: Coloring = Coloring::new(device, particle_layout, particles, centers.clone());
+        coloring.update(queue);
+        let post: Post = Post::new(device, format, width, height);
+        post.update(queue);
+        let trails: Trails = Trails::new(
+            device,
+            [
+                particle_layout,
+                &coloring.bind_group_layout,
+                &post.info_layout,
+            ],
+            &vs_mod,
+            centers,
+        );
+        let pipeline_layout: wgpu::PipelineLayout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Pipeline Layout"),
+                bind_group_layouts: &[
+                    particle_layout,
+                    &coloring.bind_group_layout,
+                    &post.info_layout,
+                ],
+                push_constant_ranges: &[],
+            });
+
+        let render_pipeline: wgpu::RenderPipeline =
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Render Pipeline"),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &vs_mod,
+                    entry_point: "vs_main",
+                    buffers: &[],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &fs_mod,
+                    entry_point: "fs_main",
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: post::HDR_FORMAT,
+                        blend: Some(post::ADDITIVE),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face: wgpu::FrontFace::Ccw,
+                    cull_mode: None,
+                    polygon_mode: wgpu::PolygonMode::Fill,
+                    unclipped_depth: false,
+                    conservative: false,
+                },
+                // sprites are summed, so they are drawn in any order without depth
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState {
+                    count: 1,
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+                multiview: None,
+            });
+
+        let projection: Projection = Projection::new(
+            device,
+            particle_layout,
+            particles,
+            &coloring.colormaps,
+            format,
+            width,
+            height,
+        );
+        Self {
+            render_pipeline,
+            coloring,
+            post,
+            trails,
+            projection,
+            overlay: Overlay::new(device, particle_layout, particles, format, width, height),
+            hud: Hud::new(device, format, width, height),
+        }
+    }
+
+    // records the per-frame compute passes, draws the particles and trails into the
+    // hdr target, tone maps it onto `target` and puts the overlays and hud on top;
+    // trails only advance when `moving`
+    pub fn draw(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        buffers: &ParticleBuffers,
+        target: &wgpu::TextureView,
+        moving: bool,
+    ) {
+        let [width, height] = self.post.info.viewport;
+        self.prepare(device, queue, encoder, buffers, moving);
+        let region: [u32; 4] = [0, 0, width as u32, height as u32];
+        self.draw_view(queue, encoder, buffers, target, region);
+        self.hud.draw(encoder, target);
+    }
+
+    // the compute passes every viewport of a frame shares
+    pub fn prepare(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        buffers: &ParticleBuffers,
+        moving: bool,
+    ) {
+        // paused frames would pile up on the same position
+        if moving {
+            self.trails.record(queue, encoder, buffers);
+        }
+        if !self.projection.enabled {
+            self.coloring.density(device, encoder, buffers);
+        }
+    }
+
+    // clears all of `target`, which the viewports' regions may not cover
+    pub fn clear(&self, encoder: &mut wgpu::CommandEncoder, target: &wgpu::TextureView) {
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Clear Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+    }
+
+    // draws the view in gpu_info into `region` of `target`, x, y, width and height
+    // in pixels; the hdr targets and overlays are sized to it
+    pub fn draw_view(
+        &mut self,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        buffers: &ParticleBuffers,
+        target: &wgpu::TextureView,
+        region: [u32; 4],
+    ) {
+        if self.projection.enabled {
+            let colormap: u32 = self.coloring.info.colormap;
+            self.projection.draw(queue, encoder, buffers, target, region, colormap);
+            self.overlay.draw(encoder, buffers, target, region);
+            return;
+        }
+        {
+            let mut rpass: wgpu::RenderPass<'_> =
+                encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some("Render Pass"),
+                    color_attachments: &[Some(self.post.scene_attachment())],
+                    depth_stencil_attachment: None,
+                });
+
+            rpass.set_pipeline(&self.render_pipeline);
+            rpass.set_bind_group(0, buffers.draw_bind_group(), &[]);
+            rpass.set_bind_group(1, &self.coloring.bind_group, &[]);
+            rpass.set_bind_group(2, &self.post.info_bind_group, &[]);
+            rpass.draw(0..6, 0..buffers.count as u32);
+            self.trails.draw(&mut rpass);
+        }
+        self.post.finish(encoder, target, region);
+        self.overlay.draw(encoder, buffers, target, region);
+    }
+}
@@ -1,84 +1,126 @@
-use wgpu::Error;
+use crate::{
+    adapter::{self, Failures},
+    error::Error,
+    options::Options,
+    render::CAMERA_POS,
+};
 use winit::window::Window;
 
+// how frames are paced, the surface falls back to vsync when the adapter lacks the
+// mode asked for
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PresentMode {
+    Vsync,
+    Mailbox,
+    Immediate,
+}
+
+impl std::str::FromStr for PresentMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "vsync" => Ok(PresentMode::Vsync),
+            "mailbox" => Ok(PresentMode::Mailbox),
+            "immediate" => Ok(PresentMode::Immediate),
+            _ => Err(format!("unknown present mode {}", s)),
+        }
+    }
+}
+
+impl PresentMode {
+    fn wgpu(self) -> wgpu::PresentMode {
+        match self {
+            PresentMode::Vsync => wgpu::PresentMode::Fifo,
+            PresentMode::Mailbox => wgpu::PresentMode::Mailbox,
+            PresentMode::Immediate => wgpu::PresentMode::Immediate,
+        }
+    }
+}
+
 pub struct Display {
     pub surface: wgpu::Surface,
     pub window: Window,
     pub config: wgpu::SurfaceConfiguration,
     pub device: wgpu::Device,
     pub queue: wgpu::Queue,
-    pub adapter: wgpu::Adapter,
+    pub failures: Failures,
     pub size: winit::dpi::PhysicalSize<u32>,
     pub camera_pos: [f32; 3],
 }
 
 impl Display {
-    pub async fn new(window: Window) -> Result<Self, Error> {
+    pub async fn new(window: Window, options: &Options) -> Result<Self, Error> {
         let size: winit::dpi::PhysicalSize<u32> = window.inner_size();
-        let instance: wgpu::Instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
-            backends: wgpu::Backends::all(),
-            dx12_shader_compiler: Default::default(),
-        });
-        let surface: wgpu::Surface = unsafe { instance.create_surface(&window) }.unwrap();
-        let adapter: wgpu::Adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::LowPower,
-                compatible_surface: Some(&surface),
-                force_fallback_adapter: false,
-            })
-            .await
-            .unwrap();
-        let (device, queue) = adapter
-            .request_device(
-                &wgpu::DeviceDescriptor {
-                    label: None,
-                    features: wgpu::Features::SPIRV_SHADER_PASSTHROUGH
-                        | wgpu::Features::VERTEX_WRITABLE_STORAGE
-                        | wgpu::Features::MAPPABLE_PRIMARY_BUFFERS,
-                    limits: if cfg!(target_arch = "wasm32") {
-                        wgpu::Limits::downlevel_webgl2_defaults()
-                    } else {
-                        wgpu::Limits::default()
-                    },
-                },
-                None,
-            )
-            .await
-            .unwrap();
+        let instance: wgpu::Instance = adapter::instance(options);
+        let surface: wgpu::Surface = unsafe { instance.create_surface(&window) }
+            .map_err(|e| Error::Init(format!("no surface for the window: {}", e)))?;
+        let adapter: wgpu::Adapter = adapter::select(&instance, options, Some(&surface))?;
+        let (device, queue, failures) = adapter::device(&adapter).await?;
+        let present_mode: PresentMode = options.present_mode;
         let surface_caps: wgpu::SurfaceCapabilities = surface.get_capabilities(&adapter);
+        // every pass onto the surface writes linear colors and leaves the encoding
+        // to the format
         let surface_format: wgpu::TextureFormat = surface_caps
             .formats
             .iter()
             .copied()
             .find(|f| f.is_srgb())
-            .unwrap_or(surface_caps.formats[0]);
+            .ok_or_else(|| {
+                Error::Init(format!(
+                    "the window has no sRGB surface format, only {:?}",
+                    surface_caps.formats
+                ))
+            })?;
+        // fifo is the one mode every surface supports
+        let present_mode: wgpu::PresentMode =
+            if surface_caps.present_modes.contains(&present_mode.wgpu()) {
+                present_mode.wgpu()
+            } else {
+                eprintln!("{:?} presentation is unsupported, using vsync", present_mode);
+                wgpu::PresentMode::Fifo
+            };
         let config: wgpu::SurfaceConfiguration = wgpu::SurfaceConfiguration {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
             format: surface_format,
-            width: size.width,
-            height: size.height,
-            present_mode: surface_caps.present_modes[0],
+            width: size.width.max(1),
+            height: size.height.max(1),
+            present_mode,
             alpha_mode: surface_caps.alpha_modes[0],
             view_formats: vec![],
         };
         surface.configure(&device, &config);
 
-        let camera_pos: [f32; 3] = [0.0, 0.0, 4e-9];
+        let camera_pos: [f32; 3] = CAMERA_POS;
         Ok(Self {
             surface,
             window,
             config,
             device,
             queue,
-            adapter,
+            failures,
             size,
             camera_pos,
         })
     }
 
-    pub fn resize(&mut self, width: u32, height: u32) {
-        self.config.width = width;
-        self.config.height = height;
+    // a minimized window keeps the last configuration until it has a size again
+    pub fn resize(&mut self, size: winit::dpi::PhysicalSize<u32>) {
+        self.size = size;
+        if self.minimized() {
+            return;
+        }
+        self.config.width = size.width;
+        self.config.height = size.height;
+        self.reconfigure();
+    }
+
+    pub fn minimized(&self) -> bool {
+        self.size.width == 0 || self.size.height == 0
+    }
+
+    // after the surface is lost, outdated or suboptimal
+    pub fn reconfigure(&self) {
         self.surface.configure(&self.device, &self.config);
     }
 }
@@ -0,0 +1,337 @@
+use {
+    crate::{
+        buffers::ParticleBuffers,
+        render::{bindings::Action, post},
+    },
+    wgpu::util::DeviceExt,
+};
+
+// positions kept per trailed particle
+const LENGTH: u32 = 64;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mode {
+    Off,
+    Tracked,
+    All,
+}
+
+impl std::str::FromStr for Mode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(Mode::Off),
+            "tracked" => Ok(Mode::Tracked),
+            "all" => Ok(Mode::All),
+            _ => Err(format!("unknown trail mode {}", s)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+pub struct TrailInfo {
+    pub length: u32,
+    // ring slot holding the latest position
+    pub head: u32,
+    // slots recorded since the last reset
+    pub filled: u32,
+    // trailed particles
+    pub count: u32,
+    pub brightness: f32,
+    _pad: [f32; 3],
+}
+
+// fading trails of the last LENGTH positions, kept in a ring buffer by a compute
+// pass every frame and drawn as lines into the hdr target; group 3 of draw.wgsl
+pub struct Trails {
+    pub info: TrailInfo,
+    pub mode: Mode,
+    // particles traced in Mode::Tracked
+    pub tracked: Vec<u32>,
+    pub buffer: wgpu::Buffer,
+    history: wgpu::Buffer,
+    indexes: wgpu::Buffer,
+    // the compute pass writes the history, the draw only reads it
+    record_layout: wgpu::BindGroupLayout,
+    record_bind_group: wgpu::BindGroup,
+    pub bind_group_layout: wgpu::BindGroupLayout,
+    pub bind_group: wgpu::BindGroup,
+    record_pipeline: wgpu::ComputePipeline,
+    pub draw_pipeline: wgpu::RenderPipeline,
+}
+
+fn storage_entry(
+    binding: u32,
+    visibility: wgpu::ShaderStages,
+    read_only: bool,
+) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only },
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    }
+}
+
+// the history is only writable to the compute stage
+fn create_layout(device: &wgpu::Device, visibility: wgpu::ShaderStages) -> wgpu::BindGroupLayout {
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("Trail Bind Group Layout"),
+        entries: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<TrailInfo>() as _),
+                },
+                count: None,
+            },
+            storage_entry(1, visibility, visibility != wgpu::ShaderStages::COMPUTE),
+            storage_entry(2, visibility, true),
+        ],
+    })
+}
+
+// the ring buffer and index list for `indexes`, at least one element each
+fn create_buffers(device: &wgpu::Device, indexes: &[u32]) -> (wgpu::Buffer, wgpu::Buffer) {
+    let history: wgpu::Buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        size: (indexes.len().max(1) * LENGTH as usize * std::mem::size_of::<[f32; 4]>()) as u64,
+        usage: wgpu::BufferUsages::STORAGE,
+        label: Some("Trail History Buffer"),
+        mapped_at_creation: false,
+    });
+    let indexes: wgpu::Buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("Trail Index Buffer"),
+        contents: bytemuck::cast_slice(if indexes.is_empty() { &[0] } else { indexes }),
+        usage: wgpu::BufferUsages::STORAGE,
+    });
+    (history, indexes)
+}
+
+fn create_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    buffer: &wgpu::Buffer,
+    history: &wgpu::Buffer,
+    indexes: &wgpu::Buffer,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("Trail Bind Group"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: history.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: indexes.as_entire_binding(),
+            },
+        ],
+    })
+}
+
+impl Trails {
+    // `layouts` are groups 0 to 2 of draw.wgsl
+    pub fn new(
+        device: &wgpu::Device,
+        layouts: [&wgpu::BindGroupLayout; 3],
+        draw_module: &wgpu::ShaderModule,
+        tracked: Vec<u32>,
+    ) -> Self {
+        let info: TrailInfo = TrailInfo {
+            length: LENGTH,
+            head: 0,
+            filled: 0,
+            count: 0,
+            brightness: 1.0,
+            _pad: [0.0; 3],
+        };
+        let buffer: wgpu::Buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("TrailInfo Buffer"),
+            contents: bytemuck::cast_slice(&[info]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let record_layout: wgpu::BindGroupLayout =
+            create_layout(device, wgpu::ShaderStages::COMPUTE);
+        let bind_group_layout: wgpu::BindGroupLayout =
+            create_layout(device, wgpu::ShaderStages::VERTEX);
+        let (history, indexes) = create_buffers(device, &[]);
+        let record_bind_group: wgpu::BindGroup =
+            create_bind_group(device, &record_layout, &buffer, &history, &indexes);
+        let bind_group: wgpu::BindGroup =
+            create_bind_group(device, &bind_group_layout, &buffer, &history, &indexes);
+
+        let source: &str = include_str!("../shaders/trail.wgsl");
+        let module: wgpu::ShaderModule =
+            device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("Trail Shader"),
+                source: wgpu::ShaderSource::Wgsl(source.into()),
+            });
+        let record_pipeline_layout: wgpu::PipelineLayout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Trail Record Pipeline Layout"),
+                bind_group_layouts: &[layouts[0], &record_layout],
+                push_constant_ranges: &[],
+            });
+        let record_pipeline: wgpu::ComputePipeline =
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some("Trail Record Pipeline"),
+                module: &module,
+                entry_point: "main",
+                layout: Some(&record_pipeline_layout),
+            });
+        let draw_layout: wgpu::PipelineLayout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Trail Draw Pipeline Layout"),
+                bind_group_layouts: &[layouts[0], layouts[1], layouts[2], &bind_group_layout],
+                push_constant_ranges: &[],
+            });
+        let draw_pipeline: wgpu::RenderPipeline =
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Trail Draw Pipeline"),
+                layout: Some(&draw_layout),
+                vertex: wgpu::VertexState {
+                    module: draw_module,
+                    entry_point: "vs_trail",
+                    buffers: &[],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: draw_module,
+                    entry_point: "fs_trail",
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: post::HDR_FORMAT,
+                        blend: Some(post::ADDITIVE),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::LineList,
+                    ..Default::default()
+                },
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+            });
+
+        Self {
+            info,
+            mode: Mode::Off,
+            tracked,
+            buffer,
+            history,
+            indexes,
+            record_layout,
+            record_bind_group,
+            bind_group_layout,
+            bind_group,
+            record_pipeline,
+            draw_pipeline,
+        }
+    }
+
+    // traces nothing, the tracked particles or all `count` particles
+    pub fn set_mode(&mut self, device: &wgpu::Device, mode: Mode, count: usize) {
+        let indexes: Vec<u32> = match mode {
+            Mode::Off => Vec::new(),
+            Mode::Tracked => self.tracked.clone(),
+            Mode::All => (0..count as u32).collect(),
+        };
+        (self.history, self.indexes) = create_buffers(device, &indexes);
+        self.record_bind_group = create_bind_group(
+            device,
+            &self.record_layout,
+            &self.buffer,
+            &self.history,
+            &self.indexes,
+        );
+        self.bind_group = create_bind_group(
+            device,
+            &self.bind_group_layout,
+            &self.buffer,
+            &self.history,
+            &self.indexes,
+        );
+        self.mode = mode;
+        self.info.count = indexes.len() as u32;
+        // thousands of overlapping lines would saturate at the tracked brightness
+        self.info.brightness = if mode == Mode::All { 0.1 } else { 1.0 };
+        self.reset();
+    }
+
+    // forgets the recorded positions, after the particles jumped
+    pub fn reset(&mut self) {
+        self.info.head = 0;
+        self.info.filled = 0;
+    }
+
+    // returns whether the action changed the trails
+    pub fn act(&mut self, device: &wgpu::Device, action: Action, count: usize) -> bool {
+        if action != Action::Trails {
+            return false;
+        }
+        let next: Mode = match self.mode {
+            Mode::Off => Mode::Tracked,
+            Mode::Tracked => Mode::All,
+            Mode::All => Mode::Off,
+        };
+        self.set_mode(device, next, count);
+        true
+    }
+
+    pub fn describe(&self) -> String {
+        match self.mode {
+            Mode::Off => "trails off".to_string(),
+            Mode::Tracked => format!("trails on {} tracked particles", self.info.count),
+            Mode::All => format!("trails on all {} particles", self.info.count),
+        }
+    }
+
+    // appends the current positions to the ring
+    pub fn record(
+        &mut self,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        buffers: &ParticleBuffers,
+    ) {
+        if self.mode == Mode::Off {
+            return;
+        }
+        if self.info.filled > 0 {
+            self.info.head = (self.info.head + 1) % LENGTH;
+        }
+        self.info.filled = (self.info.filled + 1).min(LENGTH);
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[self.info]));
+
+        let mut cpass: wgpu::ComputePass<'_> =
+            encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Trail Pass"),
+            });
+        cpass.set_pipeline(&self.record_pipeline);
+        cpass.set_bind_group(0, buffers.draw_bind_group(), &[]);
+        cpass.set_bind_group(1, &self.record_bind_group, &[]);
+        cpass.dispatch_workgroups(self.info.count.div_ceil(64), 1, 1);
+    }
+
+    // draws into a pass that already has groups 0 to 2 of draw.wgsl set
+    pub fn draw<'a>(&'a self, rpass: &mut wgpu::RenderPass<'a>) {
+        if self.mode == Mode::Off {
+            return;
+        }
+        rpass.set_pipeline(&self.draw_pipeline);
+        rpass.set_bind_group(3, &self.bind_group, &[]);
+        rpass.draw(0..2 * (LENGTH - 1), 0..self.info.count);
+    }
+}
@@ -0,0 +1,444 @@
+use {
+    crate::Galaxy,
+    cgmath::{prelude::*, Deg, Quaternion, Vector3},
+    serde::Deserialize,
+    std::{
+        fmt, fs,
+        path::{Path, PathBuf},
+    },
+};
+
+// top level of a scenario file
+#[derive(Deserialize, Clone, Debug)]
+pub struct Scenario {
+    // only the seed of the top level file is used
+    #[serde(default)]
+    pub seed: u64,
+    pub components: Vec<Component>,
+}
+
+// a scenario with every include, repeat and group resolved
+#[derive(Clone, Debug)]
+pub struct Setup {
+    pub seed: u64,
+    pub galaxies: Vec<Galaxy>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub enum Component {
+    Galaxy(Galaxy),
+    // path is relative to the file that includes it
+    Include(String),
+    // instance k gets `step` applied k times, e.g. a ring is a rotation step
+    Repeat {
+        count: u32,
+        step: Transform,
+        component: Box<Component>,
+    },
+    Group {
+        transform: Transform,
+        components: Vec<Component>,
+    },
+}
+
+// applied in order: mass scale, rotation about the origin, translation, velocity boost
+#[derive(Deserialize, Clone, Copy, Debug)]
+#[serde(default)]
+pub struct Transform {
+    pub translate: [f32; 3],
+    pub axis: [f32; 3],
+    // degrees
+    pub angle: f32,
+    pub boost: [f32; 3],
+    pub mass_scale: f32,
+}
+
+#[derive(Debug)]
+pub enum ScenarioError {
+    Io(PathBuf, std::io::Error),
+    Parse(PathBuf, ron::error::SpannedError),
+    Cycle(PathBuf),
+    Unbound(PathBuf, String),
+    // a transform turning about an axis of zero length
+    ZeroAxis(PathBuf),
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self {
+            translate: [0.0; 3],
+            axis: [0.0, 0.0, 1.0],
+            angle: 0.0,
+            boost: [0.0; 3],
+            mass_scale: 1.0,
+        }
+    }
+}
+
+impl Transform {
+    // a rotation needs an axis to normalize, without one every position would be NaN
+    fn check(&self, path: &Path) -> Result<(), ScenarioError> {
+        if self.angle != 0.0 && Vector3::from(self.axis).magnitude2() == 0.0 {
+            return Err(ScenarioError::ZeroAxis(path.to_path_buf()));
+        }
+        Ok(())
+    }
+
+    fn rotate(&self, v: [f32; 3]) -> [f32; 3] {
+        if self.angle == 0.0 {
+            return v;
+        }
+        let rotation: Quaternion<f32> =
+            Quaternion::from_axis_angle(Vector3::from(self.axis).normalize(), Deg(self.angle));
+        rotation.rotate_vector(v.into()).into()
+    }
+
+    fn position(&self, pos: [f32; 3]) -> [f32; 3] {
+        (Vector3::from(self.rotate(pos)) + Vector3::from(self.translate)).into()
+    }
+
+    fn velocity(&self, vel: [f32; 3]) -> [f32; 3] {
+        (Vector3::from(self.rotate(vel)) + Vector3::from(self.boost)).into()
+    }
+
+    pub fn apply(&self, galaxy: Galaxy) -> Galaxy {
+        match galaxy {
+            Galaxy::Particle { pos, vel, mass } => Galaxy::Particle {
+                pos: self.position(pos),
+                vel: self.velocity(vel),
+                mass: mass * self.mass_scale,
+            },
+            Galaxy::Init {
+                center_pos,
+                center_vel,
+                center_mass,
+                amount,
+                normal,
+            } => Galaxy::Init {
+                center_pos: self.position(center_pos),
+                center_vel: self.velocity(center_vel),
+                center_mass: center_mass * self.mass_scale,
+                amount,
+                normal: self.rotate(normal),
+            },
+        }
+    }
+}
+
+impl fmt::Display for ScenarioError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScenarioError::Io(path, e) => write!(f, "{}: {}", path.display(), e),
+            ScenarioError::Parse(path, e) => write!(f, "{}: {}", path.display(), e),
+            ScenarioError::Cycle(path) => write!(f, "{}: include cycle", path.display()),
+            ScenarioError::Unbound(path, name) => {
+                write!(f, "{}: no value for ${{{}}}", path.display(), name)
+            }
+            ScenarioError::ZeroAxis(path) => {
+                write!(f, "{}: rotation about a zero axis", path.display())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ScenarioError {}
+
+// files being loaded (to catch include cycles) and the ${name} substitutions
+struct Loader<'a> {
+    stack: Vec<PathBuf>,
+    vars: &'a [(String, String)],
+}
+
+impl Component {
+    fn flatten(
+        &self,
+        dir: &Path,
+        loader: &mut Loader<'_>,
+        out: &mut Vec<Galaxy>,
+    ) -> Result<(), ScenarioError> {
+        match self {
+            Component::Galaxy(galaxy) => out.push(*galaxy),
+            Component::Include(path) => out.extend(loader.load(&dir.join(path))?.galaxies),
+            Component::Repeat {
+                count,
+                step,
+                component,
+            } => {
+                step.check(loader.file())?;
+                let mut instance: Vec<Galaxy> = Vec::new();
+                component.flatten(dir, loader, &mut instance)?;
+                for _ in 0..*count {
+                    out.extend_from_slice(&instance);
+                    for galaxy in instance.iter_mut() {
+                        *galaxy = step.apply(*galaxy);
+                    }
+                }
+            }
+            Component::Group {
+                transform,
+                components,
+            } => {
+                transform.check(loader.file())?;
+                let mut group: Vec<Galaxy> = Vec::new();
+                for component in components {
+                    component.flatten(dir, loader, &mut group)?;
+                }
+                out.extend(group.into_iter().map(|galaxy| transform.apply(galaxy)));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Loader<'_> {
+    // the file being flattened
+    fn file(&self) -> &Path {
+        self.stack
+            .last()
+            .map(PathBuf::as_path)
+            .unwrap_or(Path::new(""))
+    }
+
+    fn substitute(&self, path: &Path, mut text: String) -> Result<String, ScenarioError> {
+        for (name, value) in self.vars {
+            text = text.replace(&format!("${{{}}}", name), value);
+        }
+        match text.find("${") {
+            Some(start) => {
+                let name: &str = text[start + 2..].split('}').next().unwrap_or_default();
+                Err(ScenarioError::Unbound(path.to_path_buf(), name.to_string()))
+            }
+            None => Ok(text),
+        }
+    }
+
+    fn load(&mut self, path: &Path) -> Result<Setup, ScenarioError> {
+        let path: PathBuf = path
+            .canonicalize()
+            .map_err(|e| ScenarioError::Io(path.to_path_buf(), e))?;
+        if self.stack.contains(&path) {
+            return Err(ScenarioError::Cycle(path));
+        }
+        let text: String =
+            fs::read_to_string(&path).map_err(|e| ScenarioError::Io(path.clone(), e))?;
+        let text: String = self.substitute(&path, text)?;
+        let scenario: Scenario =
+            ron::from_str(&text).map_err(|e| ScenarioError::Parse(path.clone(), e))?;
+
+        let dir: PathBuf = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        self.stack.push(path);
+        let mut galaxies: Vec<Galaxy> = Vec::new();
+        for component in &scenario.components {
+            component.flatten(&dir, self, &mut galaxies)?;
+        }
+        self.stack.pop();
+        Ok(Setup {
+            seed: scenario.seed,
+            galaxies,
+        })
+    }
+}
+
+// resolves includes, repeats and groups into the flat list init_galaxy expects,
+// replacing every ${name} in the loaded files with its value from vars
+pub fn load(path: impl AsRef<Path>, vars: &[(String, String)]) -> Result<Setup, ScenarioError> {
+    Loader {
+        stack: Vec::new(),
+        vars,
+    }
+    .load(path.as_ref())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the galaxies of a scenario given as text, which can't include other files
+    fn flatten(text: &str) -> Result<Vec<Galaxy>, ScenarioError> {
+        let scenario: Scenario = ron::from_str(text).unwrap();
+        let mut loader: Loader<'_> = Loader {
+            stack: vec![PathBuf::from("test.ron")],
+            vars: &[],
+        };
+        let mut galaxies: Vec<Galaxy> = Vec::new();
+        for component in &scenario.components {
+            component.flatten(Path::new("."), &mut loader, &mut galaxies)?;
+        }
+        Ok(galaxies)
+    }
+
+    fn close(a: [f32; 3], b: [f32; 3]) -> bool {
+        a.iter().zip(b).all(|(a, b)| (a - b).abs() < 1e-5)
+    }
+
+    fn positions(galaxies: &[Galaxy]) -> Vec<[f32; 3]> {
+        galaxies
+            .iter()
+            .map(|galaxy| match *galaxy {
+                Galaxy::Particle { pos, .. } => pos,
+                Galaxy::Init { center_pos, .. } => center_pos,
+            })
+            .collect()
+    }
+
+    const PARTICLE: &str =
+        "Galaxy(Particle(pos: (1.0, 0.0, 0.0), vel: (1.0, 0.0, 0.0), mass: 4.0))";
+
+    #[test]
+    fn group_scales_rotates_translates_then_boosts() {
+        let text: String = format!(
+            "(components: [Group(transform: (translate: (10.0, 0.0, 0.0), axis: (0.0, 0.0, 2.0), \
+             angle: 90.0, boost: (0.0, 0.0, 1.0), mass_scale: 0.5), components: [{}])])",
+            PARTICLE
+        );
+        match flatten(&text).unwrap()[..] {
+            [Galaxy::Particle { pos, vel, mass }] => {
+                assert!(close(pos, [10.0, 1.0, 0.0]), "{:?}", pos);
+                assert!(close(vel, [0.0, 1.0, 1.0]), "{:?}", vel);
+                assert_eq!(mass, 2.0);
+            }
+            ref galaxies => panic!("{:?}", galaxies),
+        }
+    }
+
+    #[test]
+    fn repeat_applies_the_step_once_more_per_instance() {
+        let text: String = format!(
+            "(components: [Repeat(count: 4, step: (angle: 90.0), component: {})])",
+            PARTICLE
+        );
+        let expected: [[f32; 3]; 4] = [
+            [1.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0],
+            [-1.0, 0.0, 0.0],
+            [0.0, -1.0, 0.0],
+        ];
+        let positions: Vec<[f32; 3]> = positions(&flatten(&text).unwrap());
+        assert_eq!(positions.len(), 4);
+        for (pos, expected) in positions.into_iter().zip(expected) {
+            assert!(close(pos, expected), "{:?} isn't {:?}", pos, expected);
+        }
+    }
+
+    #[test]
+    fn init_normals_turn_with_the_group() {
+        let text: &str = "(components: [Group(transform: (axis: (1.0, 0.0, 0.0), angle: 90.0), \
+            components: [Galaxy(Init(center_pos: (0.0, 0.0, 0.0), center_vel: (0.0, 0.0, 0.0), \
+            center_mass: 1.0, amount: 10, normal: (0.0, 0.0, 1.0)))])])";
+        match flatten(text).unwrap()[..] {
+            [Galaxy::Init { normal, amount, .. }] => {
+                assert!(close(normal, [0.0, -1.0, 0.0]), "{:?}", normal);
+                assert_eq!(amount, 10);
+            }
+            ref galaxies => panic!("{:?}", galaxies),
+        }
+    }
+
+    #[test]
+    fn zero_axis_only_matters_when_turning() {
+        let group = |angle: f32| {
+            format!(
+                "(components: [Group(transform: (axis: (0.0, 0.0, 0.0), angle: {:?}), \
+                 components: [{}])])",
+                angle, PARTICLE
+            )
+        };
+        assert!(flatten(&group(0.0)).is_ok());
+        assert!(matches!(
+            flatten(&group(30.0)),
+            Err(ScenarioError::ZeroAxis(_))
+        ));
+    }
+
+    // a directory of scenario files for the tests that include one another
+    fn files(name: &str, contents: &[(&str, &str)]) -> PathBuf {
+        let dir: PathBuf = std::env::temp_dir().join(format!("nbodysim-scenario-{}", name));
+        fs::create_dir_all(&dir).unwrap();
+        for (file, text) in contents {
+            fs::write(dir.join(file), text).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn includes_resolve_next_to_the_including_file() {
+        let top: &str = "(components: [Group(transform: (translate: (5.0, 0.0, 0.0)), \
+            components: [Include(\"one.ron\"), Include(\"one.ron\")])])";
+        let one: String = format!("(components: [{}])", PARTICLE);
+        let dir: PathBuf = files("include", &[("top.ron", top), ("one.ron", &one)]);
+        let setup: Setup = load(dir.join("top.ron"), &[]).unwrap();
+        assert_eq!(positions(&setup.galaxies), [[6.0, 0.0, 0.0]; 2]);
+    }
+
+    #[test]
+    fn include_cycles_are_errors() {
+        let dir: PathBuf = files(
+            "cycle",
+            &[
+                ("a.ron", "(components: [Include(\"b.ron\")])"),
+                ("b.ron", "(components: [Include(\"a.ron\")])"),
+            ],
+        );
+        assert!(matches!(
+            load(dir.join("a.ron"), &[]),
+            Err(ScenarioError::Cycle(_))
+        ));
+    }
+
+    fn vars(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn every_variable_is_substituted() {
+        let vars: Vec<(String, String)> = vars(&[("mass", "2e13"), ("n", "500")]);
+        let loader: Loader<'_> = Loader {
+            stack: Vec::new(),
+            vars: &vars,
+        };
+        let text: String = loader
+            .substitute(Path::new("a.ron"), "${mass} ${n} ${mass}".to_string())
+            .unwrap();
+        assert_eq!(text, "2e13 500 2e13");
+    }
+
+    #[test]
+    fn unbound_variables_are_named() {
+        let vars: Vec<(String, String)> = vars(&[("mass", "2e13")]);
+        let loader: Loader<'_> = Loader {
+            stack: Vec::new(),
+            vars: &vars,
+        };
+        match loader.substitute(Path::new("a.ron"), "${mass} ${amount}".to_string()) {
+            Err(ScenarioError::Unbound(path, name)) => {
+                assert_eq!(path, Path::new("a.ron"));
+                assert_eq!(name, "amount");
+            }
+            text => panic!("{:?}", text),
+        }
+    }
+
+    #[test]
+    fn substitution_reaches_included_files() {
+        let one: &str = "(components: [Galaxy(Particle(pos: (0.0, 0.0, 0.0), \
+            vel: (0.0, 0.0, 0.0), mass: ${mass}))])";
+        let dir: PathBuf = files(
+            "vars",
+            &[
+                ("top.ron", "(components: [Include(\"one.ron\")])"),
+                ("one.ron", one),
+            ],
+        );
+        let vars: Vec<(String, String)> = vars(&[("mass", "7.0")]);
+        match load(dir.join("top.ron"), &vars).unwrap().galaxies[..] {
+            [Galaxy::Particle { mass, .. }] => assert_eq!(mass, 7.0),
+            ref galaxies => panic!("{:?}", galaxies),
+        }
+        assert!(matches!(
+            load(dir.join("top.ron"), &[]),
+            Err(ScenarioError::Unbound(_, _))
+        ));
+    }
+}
@@ -1,8 +1,8 @@
 struct Particle {
     pos : vec3<f32>,
-    _pad1 : f32,
+    component : u32,
     vel : vec3<f32>,
-    _pad : f32,
+    id : u32,
     mass : f32,
     calibrate : f32,
     _pad3 : vec2<f32>,
@@ -27,33 +27,61 @@ struct DataCurrent {
 @group(0) @binding(1) var<storage, read> dataOld : DataOld;
 @group(0) @binding(2) var<storage, read_write> dataCurrent : DataCurrent;
 
+// positions, masses and calibrates of one tile, WORKGROUP_SIZE is set by compute.rs
+var<workgroup> tile : array<vec4<f32>, WORKGROUP_SIZE>;
+var<workgroup> tileCalibrate : array<f32, WORKGROUP_SIZE>;
+
 fn length2(v : vec3<f32>) -> f32 {
     return v.x * v.x + v.y * v.y + v.z * v.z;
 }
 
-// refactor workgroup?
 @compute
-@workgroup_size(256)
-fn main(@builtin(global_invocation_id) global_invocation_id: vec3<u32>) {
+@workgroup_size(WORKGROUP_SIZE)
+fn main(@builtin(global_invocation_id) global_invocation_id: vec3<u32>,
+        @builtin(local_invocation_id) local_invocation_id: vec3<u32>) {
     let i: u32 = global_invocation_id.x;
+    let local: u32 = local_invocation_id.x;
+    let n: u32 = gpu_info.particles;
     let G: f32 = f32(6.6e-31);
 
-    if (gpu_info.motion > 0.0) {
+    // threads past the end still load their share of every tile
+    var particle : Particle;
+    if (i < n) {
+        particle = dataOld.old[i];
+    }
+    // LEADING_DRIFT is set by compute.rs, 0.5 for leapfrog and 0.0 for euler
+    let before: f32 = LEADING_DRIFT * gpu_info.motion;
+    let after: f32 = gpu_info.motion - before;
+    if (gpu_info.motion != 0.0) {
+        particle.pos = particle.pos + particle.vel * before;
         var temp : vec3<f32> = vec3<f32>(0.0, 0.0, 0.0);
-        for (var j : u32 = 0u; j < u32(gpu_info.particles); j = j + 1u) {
-            if (j == i) {
-                continue;
-            }
-            if (dataOld.old[j].mass == 0.0) {
-                break;
+        for (var start : u32 = 0u; start < n; start = start + WORKGROUP_SIZE) {
+            let j: u32 = start + local;
+            if (j < n) {
+                let other : Particle = dataOld.old[j];
+                tile[local] = vec4<f32>(other.pos + other.vel * before, other.mass);
+                tileCalibrate[local] = dataOld.old[j].calibrate;
+            } else {
+                tile[local] = vec4<f32>(0.0, 0.0, 0.0, 0.0);
+                tileCalibrate[local] = 1.0;
             }
+            workgroupBarrier();
 
-            var diff : vec3<f32> = vec3<f32>(dataOld.old[j].pos - dataOld.old[i].pos);
-            temp = temp + (normalize(diff) * (dataOld.old[j].mass) / (length2(diff) +
-            dataOld.old[j].calibrate));
+            for (var k : u32 = 0u; k < WORKGROUP_SIZE; k = k + 1u) {
+                let mass: f32 = tile[k].w;
+                if (start + k == i || mass == 0.0) {
+                    continue;
+                }
+                var diff : vec3<f32> = tile[k].xyz - particle.pos;
+                temp = temp + (normalize(diff) * mass / (length2(diff) + tileCalibrate[k]));
+            }
+            workgroupBarrier();
         }
-        dataCurrent.data[i].vel = dataCurrent.data[i].vel + vec3<f32>(temp * G * gpu_info.motion);
-        dataCurrent.data[i].pos = dataCurrent.data[i].pos + dataCurrent.data[i].vel *
-        gpu_info.motion;
+        particle.vel = particle.vel + vec3<f32>(temp * G * gpu_info.motion);
+        particle.pos = particle.pos + particle.vel * after;
+    }
+    // the particle is always written since the buffers swap roles every step
+    if (i < n) {
+        dataCurrent.data[i] = particle;
     }
 }
@@ -0,0 +1,55 @@
+struct Particle {
+    pos : vec3<f32>,
+    component : u32,
+    vel : vec3<f32>,
+    id : u32,
+    mass : f32,
+    calibrate : f32,
+    _pad3 : vec2<f32>,
+};
+
+struct Gpu_Info {
+    matrix : mat4x4<f32>,
+    particles : u32,
+    motion : f32,
+    _pad : vec2<f32>,
+};
+
+struct ColorInfo {
+    mode : u32,
+    colormap : u32,
+    center : u32,
+    log : u32,
+    min : f32,
+    max : f32,
+    radius : f32,
+    _pad : f32,
+};
+
+struct DataCurrent {
+    data : array<Particle>,
+};
+
+@group(0) @binding(0) var<uniform> gpu_info : Gpu_Info;
+@group(0) @binding(2) var<storage, read> dataCurrent : DataCurrent;
+@group(1) @binding(0) var<uniform> color_info : ColorInfo;
+@group(1) @binding(2) var<storage, read_write> density : array<f32>;
+
+// kernel-weighted mass within color_info.radius, sum of m * (1 - r^2 / h^2)^3
+@compute
+@workgroup_size(64)
+fn main(@builtin(global_invocation_id) global_invocation_id: vec3<u32>) {
+    let i: u32 = global_invocation_id.x;
+    if (i >= gpu_info.particles) {
+        return;
+    }
+    let h2: f32 = color_info.radius * color_info.radius;
+    let pos: vec3<f32> = dataCurrent.data[i].pos;
+    var sum: f32 = 0.0;
+    for (var j : u32 = 0u; j < gpu_info.particles; j = j + 1u) {
+        let diff: vec3<f32> = dataCurrent.data[j].pos - pos;
+        let w: f32 = max(1.0 - dot(diff, diff) / h2, 0.0);
+        sum = sum + dataCurrent.data[j].mass * w * w * w;
+    }
+    density[i] = sum;
+}
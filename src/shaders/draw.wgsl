@@ -1,9 +1,8 @@
-
 struct Particle {
     pos : vec3<f32>,
-    _pad1 : f32,
+    component : u32,
     vel : vec3<f32>,
-    _pad : f32,
+    id : u32,
     mass : f32,
     calibrate : f32,
     _pad2 : vec2<f32>,
@@ -16,43 +15,193 @@ struct Gpu_Info {
     _pad : vec2<f32>,
 };
 
+struct ColorInfo {
+    mode : u32,
+    colormap : u32,
+    center : u32,
+    log : u32,
+    min : f32,
+    max : f32,
+    radius : f32,
+    _pad : f32,
+};
+
+struct PostInfo {
+    viewport : vec2<f32>,
+    scale : f32,
+    size : f32,
+    min_pixels : f32,
+    brightness : f32,
+    exposure : f32,
+    bloom : f32,
+    threshold : f32,
+    knee : f32,
+    picked : u32,
+    _pad : f32,
+};
+
+struct TrailInfo {
+    length : u32,
+    head : u32,
+    filled : u32,
+    count : u32,
+    brightness : f32,
+    _pad1 : f32,
+    _pad2 : f32,
+    _pad3 : f32,
+};
+
 struct DataCurrent {
     data : array<Particle>,
 };
 
 struct VertexIn {
     @builtin(vertex_index) vertexIndex : u32,
+    @builtin(instance_index) instanceIndex : u32,
 };
 struct VertexOut {
     @location(0) fragColor : vec3<f32>,
     @builtin(position) pos : vec4<f32>,
-    @location(1) pointSize : f32,
+    @location(1) corner : vec2<f32>,
+    @location(2) intensity : f32,
 };
 
 @group(0) @binding(0) var<uniform> gpu_info : Gpu_Info;
-@group(0) @binding(2) var<storage, read_write> dataCurrent : DataCurrent;
+@group(0) @binding(2) var<storage, read> dataCurrent : DataCurrent;
+@group(1) @binding(0) var<uniform> color_info : ColorInfo;
+@group(1) @binding(1) var<storage, read> colormaps : array<vec4<f32>>;
+@group(1) @binding(2) var<storage, read> density : array<f32>;
+@group(2) @binding(0) var<uniform> post : PostInfo;
+@group(3) @binding(0) var<uniform> trail : TrailInfo;
+@group(3) @binding(1) var<storage, read> history : array<vec4<f32>>;
+@group(3) @binding(2) var<storage, read> trailIndexes : array<u32>;
+
+// samples per colormap, see COLORMAPS in render/color.rs
+const STOPS : u32 = 9u;
+
+fn colormap(t : f32) -> vec3<f32> {
+    let x : f32 = clamp(t, 0.0, 1.0) * f32(STOPS - 1u);
+    let k : u32 = min(u32(x), STOPS - 2u);
+    let base : u32 = color_info.colormap * STOPS + k;
+    return mix(colormaps[base].rgb, colormaps[base + 1u].rgb, x - f32(k));
+}
+
+fn palette(component : u32) -> vec3<f32> {
+    switch (component % 6u) {
+        case 0u: { return vec3<f32>(1.0, 0.5, 0.67); }
+        case 1u: { return vec3<f32>(0.4, 0.5, 1.0); }
+        case 2u: { return vec3<f32>(0.55, 0.9, 0.45); }
+        case 3u: { return vec3<f32>(1.0, 0.8, 0.3); }
+        case 4u: { return vec3<f32>(0.7, 0.45, 1.0); }
+        default: { return vec3<f32>(0.35, 0.9, 0.9); }
+    }
+}
+
+fn value(i : u32) -> f32 {
+    let particle : Particle = dataCurrent.data[i];
+    switch (color_info.mode) {
+        case 1u: { return length(particle.vel); }
+        case 2u: { return 0.5 * particle.mass * dot(particle.vel, particle.vel); }
+        case 3u: { return density[i]; }
+        case 4u: {
+            var center : vec3<f32> = vec3<f32>(0.0, 0.0, 0.0);
+            if (color_info.center < gpu_info.particles) {
+                center = dataCurrent.data[color_info.center].pos;
+            }
+            return length(particle.pos - center);
+        }
+        default: { return f32(particle.id); }
+    }
+}
+
+// position of the value within [min, max], in log10 space when enabled
+fn normalized(v : f32) -> f32 {
+    if (color_info.log == 1u) {
+        let lo : f32 = log2(max(color_info.min, 1e-37));
+        let hi : f32 = log2(max(color_info.max, 1e-37));
+        return (log2(max(v, 1e-37)) - lo) / (hi - lo);
+    }
+    return (v - color_info.min) / (color_info.max - color_info.min);
+}
+
+fn particleColor(i : u32) -> vec3<f32> {
+    if (dataCurrent.data[i].mass > 1E33) {
+        return vec3<f32>(0.0, 0.0, 0.0);
+    }
+    if (color_info.mode == 0u) {
+        return palette(dataCurrent.data[i].component);
+    }
+    return colormap(normalized(value(i)));
+}
+
+// each particle is an instance of two triangles facing the camera
 @vertex
 fn vs_main(input: VertexIn) -> VertexOut {
-    let i : i32 = i32(input.vertexIndex);
+    let i : i32 = i32(input.instanceIndex);
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, -1.0),
+        vec2<f32>(-1.0, 1.0),
+        vec2<f32>(-1.0, 1.0),
+        vec2<f32>(1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+    );
+    let corner : vec2<f32> = corners[input.vertexIndex];
     var output: VertexOut;
-    if (dataCurrent.data[i].mass < 0.0) {
-        output.pos = vec4<f32> (dataCurrent.data[i].pos, 1.0);
-    }
-    output.pos = gpu_info.matrix * vec4<f32>(dataCurrent.data[i].pos, 1.0);
+    let center : vec4<f32> = gpu_info.matrix * vec4<f32>(dataCurrent.data[i].pos, 1.0);
 
-    if (dataCurrent.data[i].mass > 1E33) {
-        output.fragColor = vec3<f32>(0.0, 0.0, 0.0);
-    } else {
-        if (i < i32(gpu_info.particles) / 2 + 1) {
-            output.fragColor = vec3<f32>(1.0, 0.5, 0.67);
-        } else {
-            output.fragColor = vec3<f32>(0.4, 0.5, 1.0);
-        }
+    // radius in pixels shrinks with distance but never below min_pixels,
+    // a sprite drawn larger than its size is dimmed to keep its total light
+    let pixels : f32 = max(post.size * post.scale / center.w * post.viewport.y * 0.5, 0.0);
+    var shown : f32 = max(pixels, post.min_pixels);
+    output.intensity = post.brightness * (pixels / shown) * (pixels / shown);
+    output.fragColor = particleColor(u32(i));
+    // the picked particle is a large bright green sprite
+    if (u32(i) == post.picked) {
+        shown = max(shown, 8.0);
+        output.intensity = 4.0;
+        output.fragColor = vec3<f32>(0.2, 1.0, 0.3);
     }
+    output.pos = center + vec4<f32>(corner * shown * 2.0 / post.viewport * center.w, 0.0, 0.0);
+    output.corner = corner;
     return output;
 }
 
+// gaussian falloff, summed additively into the hdr target
 @fragment
 fn fs_main(in: VertexOut) -> @location(0) vec4<f32> {
-  return vec4(in.fragColor, 1.0);
+    let r2 : f32 = dot(in.corner, in.corner);
+    if (r2 > 1.0) {
+        discard;
+    }
+    return vec4<f32>(in.fragColor * in.intensity * exp(-4.0 * r2), 1.0);
+}
+
+struct TrailOut {
+    @builtin(position) pos : vec4<f32>,
+    @location(0) color : vec3<f32>,
+};
+
+// segment v / 2 of trail t joins the positions v / 2 and v / 2 + 1 steps old,
+// segments reaching past what has been recorded are moved off screen
+@vertex
+fn vs_trail(@builtin(vertex_index) v : u32, @builtin(instance_index) t : u32) -> TrailOut {
+    var output : TrailOut;
+    if (v / 2u + 1u >= trail.filled) {
+        output.pos = vec4<f32>(2.0, 2.0, 2.0, 1.0);
+        output.color = vec3<f32>(0.0, 0.0, 0.0);
+        return output;
+    }
+    let age : u32 = v / 2u + v % 2u;
+    let slot : u32 = (trail.head + trail.length - age) % trail.length;
+    output.pos = gpu_info.matrix * vec4<f32>(history[slot * trail.count + t].xyz, 1.0);
+    let fade : f32 = 1.0 - f32(age) / f32(trail.length);
+    let i : u32 = min(trailIndexes[t], gpu_info.particles - 1u);
+    output.color = particleColor(i) * trail.brightness * fade;
+    return output;
+}
+
+@fragment
+fn fs_trail(in : TrailOut) -> @location(0) vec4<f32> {
+    return vec4<f32>(in.color, 1.0);
 }
@@ -0,0 +1,61 @@
+struct HudInfo {
+    viewport : vec2<f32>,
+    // screen pixels per font pixel
+    scale : f32,
+    count : u32,
+};
+
+@group(0) @binding(0) var<uniform> hud : HudInfo;
+// five columns per printable ascii character, bit 0 is the top row
+@group(0) @binding(1) var<storage, read> font : array<u32>;
+// code | column << 8 | line << 20 per character
+@group(0) @binding(2) var<storage, read> glyphs : array<u32>;
+
+struct GlyphOut {
+    @builtin(position) pos : vec4<f32>,
+    @location(0) local : vec2<f32>,
+    @location(1) @interpolate(flat) code : u32,
+};
+
+// a cell is 6x10 font pixels: 5 columns and a gap, 8 rows and the line spacing
+const CELL : vec2<f32> = vec2<f32>(6.0, 10.0);
+const MARGIN : vec2<f32> = vec2<f32>(4.0, 4.0);
+
+@vertex
+fn vs_main(@builtin(vertex_index) v : u32, @builtin(instance_index) t : u32) -> GlyphOut {
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(0.0, 0.0),
+        vec2<f32>(1.0, 0.0),
+        vec2<f32>(0.0, 1.0),
+        vec2<f32>(0.0, 1.0),
+        vec2<f32>(1.0, 0.0),
+        vec2<f32>(1.0, 1.0),
+    );
+    let glyph : u32 = glyphs[t];
+    let cell : vec2<f32> = vec2<f32>(f32((glyph >> 8u) & 0xFFFu), f32(glyph >> 20u));
+    let local : vec2<f32> = corners[v] * CELL;
+    let pixel : vec2<f32> = (MARGIN + cell * CELL + local) * hud.scale;
+    var output : GlyphOut;
+    output.pos = vec4<f32>(
+        pixel.x / hud.viewport.x * 2.0 - 1.0,
+        1.0 - pixel.y / hud.viewport.y * 2.0,
+        0.0,
+        1.0,
+    );
+    output.local = local;
+    output.code = glyph & 0xFFu;
+    return output;
+}
+
+// lit font pixels over a translucent backdrop
+@fragment
+fn fs_main(in : GlyphOut) -> @location(0) vec4<f32> {
+    let x : u32 = u32(in.local.x);
+    let y : u32 = u32(in.local.y);
+    if (x < 5u && y < 8u && in.code >= 32u && in.code < 127u) {
+        if (((font[(in.code - 32u) * 5u + x] >> y) & 1u) == 1u) {
+            return vec4<f32>(1.0, 1.0, 1.0, 1.0);
+        }
+    }
+    return vec4<f32>(0.0, 0.0, 0.0, 0.5);
+}
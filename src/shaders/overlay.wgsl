@@ -0,0 +1,77 @@
+struct Particle {
+    pos : vec3<f32>,
+    component : u32,
+    vel : vec3<f32>,
+    id : u32,
+    mass : f32,
+    calibrate : f32,
+    _pad3 : vec2<f32>,
+};
+
+struct Gpu_Info {
+    matrix : mat4x4<f32>,
+    particles : u32,
+    motion : f32,
+    _pad : vec2<f32>,
+};
+
+struct OverlayInfo {
+    // the center of mass, marked after the galaxy centers
+    center_of_mass : vec3<f32>,
+    // half the width of a marker
+    marker_pixels : f32,
+    viewport : vec2<f32>,
+    // galaxy centers in markers
+    count : u32,
+    _pad : f32,
+};
+
+struct DataCurrent {
+    data : array<Particle>,
+};
+
+@group(0) @binding(0) var<uniform> gpu_info : Gpu_Info;
+@group(0) @binding(2) var<storage, read> dataCurrent : DataCurrent;
+@group(1) @binding(0) var<uniform> overlay : OverlayInfo;
+@group(1) @binding(1) var<storage, read> markers : array<u32>;
+
+struct LineOut {
+    @builtin(position) pos : vec4<f32>,
+    @location(0) color : vec4<f32>,
+};
+
+// grid, axes and scale bar, in world space
+@vertex
+fn vs_lines(@location(0) pos : vec3<f32>, @location(1) color : vec4<f32>) -> LineOut {
+    var output : LineOut;
+    output.pos = gpu_info.matrix * vec4<f32>(pos, 1.0);
+    output.color = color;
+    return output;
+}
+
+// a cross over particle markers[t], or over the center of mass past the end
+@vertex
+fn vs_markers(@builtin(vertex_index) v : u32, @builtin(instance_index) t : u32) -> LineOut {
+    var ends = array<vec2<f32>, 4>(
+        vec2<f32>(-1.0, 0.0),
+        vec2<f32>(1.0, 0.0),
+        vec2<f32>(0.0, -1.0),
+        vec2<f32>(0.0, 1.0),
+    );
+    var output : LineOut;
+    var at : vec3<f32> = overlay.center_of_mass;
+    output.color = vec4<f32>(1.0, 1.0, 1.0, 0.9);
+    if (t < overlay.count) {
+        at = dataCurrent.data[min(markers[t], gpu_info.particles - 1u)].pos;
+        output.color = vec4<f32>(1.0, 0.8, 0.2, 0.9);
+    }
+    let center : vec4<f32> = gpu_info.matrix * vec4<f32>(at, 1.0);
+    let offset : vec2<f32> = ends[v] * overlay.marker_pixels * 2.0 / overlay.viewport * center.w;
+    output.pos = center + vec4<f32>(offset, 0.0, 0.0);
+    return output;
+}
+
+@fragment
+fn fs_main(in : LineOut) -> @location(0) vec4<f32> {
+    return in.color;
+}
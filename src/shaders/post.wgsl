@@ -0,0 +1,92 @@
+struct PostInfo {
+    viewport : vec2<f32>,
+    scale : f32,
+    size : f32,
+    min_pixels : f32,
+    brightness : f32,
+    exposure : f32,
+    bloom : f32,
+    threshold : f32,
+    knee : f32,
+    picked : u32,
+    _pad : f32,
+};
+
+@group(0) @binding(0) var<uniform> post : PostInfo;
+@group(1) @binding(0) var source : texture_2d<f32>;
+@group(1) @binding(1) var bilinear : sampler;
+@group(1) @binding(2) var glow : texture_2d<f32>;
+
+struct FullscreenOut {
+    @builtin(position) pos : vec4<f32>,
+    @location(0) uv : vec2<f32>,
+};
+
+// one triangle covering the target, uv runs top to bottom like the textures
+@vertex
+fn vs_fullscreen(@builtin(vertex_index) v : u32) -> FullscreenOut {
+    var output : FullscreenOut;
+    let uv : vec2<f32> = vec2<f32>(f32((v << 1u) & 2u), f32(v & 2u));
+    output.pos = vec4<f32>(uv * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0), 0.0, 1.0);
+    output.uv = uv;
+    return output;
+}
+
+fn texel() -> vec2<f32> {
+    return 1.0 / vec2<f32>(textureDimensions(source));
+}
+
+// four bilinear taps averaging a 4x4 block of the source
+fn box4(uv : vec2<f32>) -> vec3<f32> {
+    let d : vec2<f32> = texel();
+    var sum : vec3<f32> = textureSample(source, bilinear, uv + vec2<f32>(-d.x, -d.y)).rgb;
+    sum = sum + textureSample(source, bilinear, uv + vec2<f32>(d.x, -d.y)).rgb;
+    sum = sum + textureSample(source, bilinear, uv + vec2<f32>(-d.x, d.y)).rgb;
+    sum = sum + textureSample(source, bilinear, uv + vec2<f32>(d.x, d.y)).rgb;
+    return sum * 0.25;
+}
+
+// first bloom level, keeps only what is brighter than the threshold with a soft knee
+@fragment
+fn fs_prefilter(in : FullscreenOut) -> @location(0) vec4<f32> {
+    let color : vec3<f32> = box4(in.uv);
+    let brightness : f32 = max(color.r, max(color.g, color.b));
+    var soft : f32 = clamp(brightness - post.threshold + post.knee, 0.0, 2.0 * post.knee);
+    soft = soft * soft / (4.0 * post.knee + 1e-5);
+    let weight : f32 = max(soft, brightness - post.threshold) / max(brightness, 1e-5);
+    return vec4<f32>(color * weight, 1.0);
+}
+
+@fragment
+fn fs_down(in : FullscreenOut) -> @location(0) vec4<f32> {
+    return vec4<f32>(box4(in.uv), 1.0);
+}
+
+// 3x3 tent filter of the smaller level, added onto the larger one
+@fragment
+fn fs_up(in : FullscreenOut) -> @location(0) vec4<f32> {
+    let d : vec2<f32> = texel();
+    var sum : vec3<f32> = textureSample(source, bilinear, in.uv).rgb * 4.0;
+    sum = sum + textureSample(source, bilinear, in.uv + vec2<f32>(-d.x, 0.0)).rgb * 2.0;
+    sum = sum + textureSample(source, bilinear, in.uv + vec2<f32>(d.x, 0.0)).rgb * 2.0;
+    sum = sum + textureSample(source, bilinear, in.uv + vec2<f32>(0.0, -d.y)).rgb * 2.0;
+    sum = sum + textureSample(source, bilinear, in.uv + vec2<f32>(0.0, d.y)).rgb * 2.0;
+    sum = sum + textureSample(source, bilinear, in.uv + vec2<f32>(-d.x, -d.y)).rgb;
+    sum = sum + textureSample(source, bilinear, in.uv + vec2<f32>(d.x, -d.y)).rgb;
+    sum = sum + textureSample(source, bilinear, in.uv + vec2<f32>(-d.x, d.y)).rgb;
+    sum = sum + textureSample(source, bilinear, in.uv + vec2<f32>(d.x, d.y)).rgb;
+    return vec4<f32>(sum / 16.0, 1.0);
+}
+
+// ACES filmic curve fitted by Krzysztof Narkowicz
+fn aces(x : vec3<f32>) -> vec3<f32> {
+    return clamp(x * (2.51 * x + 0.03) / (x * (2.43 * x + 0.59) + 0.14), vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+// the target is sRGB, so the linear result is encoded on write
+@fragment
+fn fs_tonemap(in : FullscreenOut) -> @location(0) vec4<f32> {
+    let hdr : vec3<f32> = textureSample(source, bilinear, in.uv).rgb;
+    let bloom : vec3<f32> = textureSample(glow, bilinear, in.uv).rgb;
+    return vec4<f32>(aces((hdr + bloom * post.bloom) * post.exposure), 1.0);
+}
@@ -0,0 +1,127 @@
+struct Particle {
+    pos : vec3<f32>,
+    component : u32,
+    vel : vec3<f32>,
+    id : u32,
+    mass : f32,
+    calibrate : f32,
+    _pad3 : vec2<f32>,
+};
+
+struct Gpu_Info {
+    matrix : mat4x4<f32>,
+    particles : u32,
+    motion : f32,
+    _pad : vec2<f32>,
+};
+
+struct ProjectionInfo {
+    // cells across and down
+    grid : vec2<u32>,
+    // pixels per cell side
+    cell : f32,
+    // mass of one fixed point count
+    unit : f32,
+    // powers of ten below the peak the colormap spans
+    decades : f32,
+    colormap : u32,
+    // top left pixel of the viewport
+    origin : vec2<f32>,
+};
+
+struct DataCurrent {
+    data : array<Particle>,
+};
+
+@group(0) @binding(0) var<uniform> gpu_info : Gpu_Info;
+@group(0) @binding(2) var<storage, read> dataCurrent : DataCurrent;
+@group(1) @binding(0) var<uniform> info : ProjectionInfo;
+// mass per cell in counts of info.unit, there are no float atomics
+@group(1) @binding(1) var<storage, read_write> grid : array<atomic<u32>>;
+@group(1) @binding(2) var<storage, read_write> peak : atomic<u32>;
+@group(1) @binding(3) var<storage, read> colormaps : array<vec4<f32>>;
+
+// samples per colormap, see COLORMAPS in render/color.rs
+const STOPS : u32 = 9u;
+
+@compute
+@workgroup_size(64)
+fn clear(@builtin(global_invocation_id) global_invocation_id : vec3<u32>) {
+    let i : u32 = global_invocation_id.x;
+    if (i == 0u) {
+        atomicStore(&peak, 0u);
+    }
+    if (i < info.grid.x * info.grid.y) {
+        atomicStore(&grid[i], 0u);
+    }
+}
+
+fn add(x : i32, y : i32, counts : f32) {
+    if (x < 0 || y < 0 || x >= i32(info.grid.x) || y >= i32(info.grid.y) || counts < 0.5) {
+        return;
+    }
+    atomicAdd(&grid[u32(y) * info.grid.x + u32(x)], u32(counts + 0.5));
+}
+
+// spreads each particle's mass over the four cells around its projected
+// position, cloud in cell
+@compute
+@workgroup_size(64)
+fn deposit(@builtin(global_invocation_id) global_invocation_id : vec3<u32>) {
+    let i : u32 = global_invocation_id.x;
+    if (i >= gpu_info.particles) {
+        return;
+    }
+    let particle : Particle = dataCurrent.data[i];
+    let clip : vec4<f32> = gpu_info.matrix * vec4<f32>(particle.pos, 1.0);
+    if (clip.w <= 0.0 || particle.mass <= 0.0) {
+        return;
+    }
+    let ndc : vec2<f32> = clip.xy / clip.w;
+    let at : vec2<f32> = vec2<f32>(ndc.x + 1.0, 1.0 - ndc.y) * 0.5 * vec2<f32>(info.grid) - 0.5;
+    let base : vec2<f32> = floor(at);
+    let f : vec2<f32> = at - base;
+    let counts : f32 = particle.mass / info.unit;
+    let x : i32 = i32(base.x);
+    let y : i32 = i32(base.y);
+    add(x, y, counts * (1.0 - f.x) * (1.0 - f.y));
+    add(x + 1, y, counts * f.x * (1.0 - f.y));
+    add(x, y + 1, counts * (1.0 - f.x) * f.y);
+    add(x + 1, y + 1, counts * f.x * f.y);
+}
+
+@compute
+@workgroup_size(64)
+fn find_peak(@builtin(global_invocation_id) global_invocation_id : vec3<u32>) {
+    let i : u32 = global_invocation_id.x;
+    if (i < info.grid.x * info.grid.y) {
+        atomicMax(&peak, atomicLoad(&grid[i]));
+    }
+}
+
+@vertex
+fn vs_fullscreen(@builtin(vertex_index) v : u32) -> @builtin(position) vec4<f32> {
+    let uv : vec2<f32> = vec2<f32>(f32((v << 1u) & 2u), f32(v & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+fn colormap(t : f32) -> vec3<f32> {
+    let x : f32 = clamp(t, 0.0, 1.0) * f32(STOPS - 1u);
+    let k : u32 = min(u32(x), STOPS - 2u);
+    let base : u32 = info.colormap * STOPS + k;
+    return mix(colormaps[base].rgb, colormaps[base + 1u].rgb, x - f32(k));
+}
+
+// log of the cell's mass relative to the peak, empty cells are black
+@fragment
+fn fs_main(@builtin(position) pos : vec4<f32>) -> @location(0) vec4<f32> {
+    let cell : vec2<u32> = min(vec2<u32>((pos.xy - info.origin) / info.cell), info.grid - 1u);
+    let counts : u32 = atomicLoad(&grid[cell.y * info.grid.x + cell.x]);
+    let top : u32 = atomicLoad(&peak);
+    if (counts == 0u || top == 0u) {
+        return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+    }
+    let t : f32 = 1.0 + log2(f32(counts) / f32(top)) / (info.decades * log2(10.0));
+    // the colormaps are srgb values and the target encodes linear ones
+    return vec4<f32>(pow(colormap(t), vec3<f32>(2.2)), 1.0);
+}
@@ -0,0 +1,49 @@
+struct Particle {
+    pos : vec3<f32>,
+    component : u32,
+    vel : vec3<f32>,
+    id : u32,
+    mass : f32,
+    calibrate : f32,
+    _pad3 : vec2<f32>,
+};
+
+struct Gpu_Info {
+    matrix : mat4x4<f32>,
+    particles : u32,
+    motion : f32,
+    _pad : vec2<f32>,
+};
+
+struct TrailInfo {
+    length : u32,
+    head : u32,
+    filled : u32,
+    count : u32,
+    brightness : f32,
+    _pad1 : f32,
+    _pad2 : f32,
+    _pad3 : f32,
+};
+
+struct DataCurrent {
+    data : array<Particle>,
+};
+
+@group(0) @binding(0) var<uniform> gpu_info : Gpu_Info;
+@group(0) @binding(2) var<storage, read> dataCurrent : DataCurrent;
+@group(1) @binding(0) var<uniform> trail : TrailInfo;
+@group(1) @binding(1) var<storage, read_write> history : array<vec4<f32>>;
+@group(1) @binding(2) var<storage, read> trailIndexes : array<u32>;
+
+// stores the position of every trailed particle in the ring slot trail.head
+@compute
+@workgroup_size(64)
+fn main(@builtin(global_invocation_id) global_invocation_id: vec3<u32>) {
+    let t: u32 = global_invocation_id.x;
+    if (t >= trail.count) {
+        return;
+    }
+    let i: u32 = min(trailIndexes[t], gpu_info.particles - 1u);
+    history[trail.head * trail.count + t] = vec4<f32>(dataCurrent.data[i].pos, 1.0);
+}
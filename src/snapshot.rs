@@ -0,0 +1,115 @@
+use {
+    crate::Particle,
+    std::{
+        fs, io,
+        path::{Path, PathBuf},
+    },
+};
+
+const MAGIC: [u8; 4] = *b"NBSN";
+
+// file layout: Header followed by `count` raw Particles
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+struct Header {
+    magic: [u8; 4],
+    count: u32,
+    step: u64,
+    time: f64,
+}
+
+#[derive(Clone, Debug)]
+pub struct Snapshot {
+    pub step: u64,
+    pub time: f64,
+    pub particles: Vec<Particle>,
+}
+
+pub fn path(dir: &Path, step: u64) -> PathBuf {
+    dir.join(format!("snapshot_{:08}.bin", step))
+}
+
+fn encode(step: u64, time: f64, particles: &[Particle]) -> Vec<u8> {
+    let header: Header = Header {
+        magic: MAGIC,
+        count: particles.len() as u32,
+        step,
+        time,
+    };
+    let mut bytes: Vec<u8> = bytemuck::bytes_of(&header).to_vec();
+    bytes.extend_from_slice(bytemuck::cast_slice(particles));
+    bytes
+}
+
+pub fn write(path: &Path, step: u64, time: f64, particles: &[Particle]) -> io::Result<()> {
+    fs::write(path, encode(step, time, particles))
+}
+
+fn decode(bytes: &[u8]) -> io::Result<Snapshot> {
+    let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+    let header_size: usize = std::mem::size_of::<Header>();
+    if bytes.len() < header_size {
+        return Err(invalid("snapshot too short"));
+    }
+    let header: Header = bytemuck::pod_read_unaligned(&bytes[..header_size]);
+    if header.magic != MAGIC {
+        return Err(invalid("not a snapshot file"));
+    }
+    let body: &[u8] = &bytes[header_size..];
+    if body.len() != header.count as usize * std::mem::size_of::<Particle>() {
+        return Err(invalid("snapshot particle count does not match its size"));
+    }
+    let particles: Vec<Particle> = body
+        .chunks_exact(std::mem::size_of::<Particle>())
+        .map(bytemuck::pod_read_unaligned)
+        .collect();
+    Ok(Snapshot {
+        step: header.step,
+        time: header.time,
+        particles,
+    })
+}
+
+pub fn read(path: &Path) -> io::Result<Snapshot> {
+    decode(&fs::read(path)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_then_raw_particles() {
+        let particles: Vec<Particle> = (0..3)
+            .map(|i| Particle::new([i as f32, -1.0, 2.5], [0.0, 1e-14, 0.0], 1e8, 1e-19))
+            .collect();
+        let bytes: Vec<u8> = encode(42, 0.125, &particles);
+        let header_size: usize = std::mem::size_of::<Header>();
+        let header: Header = bytemuck::pod_read_unaligned(&bytes[..header_size]);
+        assert_eq!(header.magic, *b"NBSN");
+        assert_eq!((header.count, header.step, header.time), (3, 42, 0.125));
+        let body: &[u8] = bytemuck::cast_slice(&particles);
+        assert_eq!(&bytes[header_size..], body);
+    }
+
+    #[test]
+    fn round_trip() {
+        let particles: Vec<Particle> = (0..3)
+            .map(|i| Particle::new([i as f32, -1.0, 2.5], [0.0, 1e-14, i as f32], 1e8, 1e-19))
+            .collect();
+        let snapshot: Snapshot = decode(&encode(42, 0.125, &particles)).unwrap();
+        assert_eq!((snapshot.step, snapshot.time), (42, 0.125));
+        let read: &[u8] = bytemuck::cast_slice(&snapshot.particles);
+        assert_eq!(read, bytemuck::cast_slice::<Particle, u8>(&particles));
+    }
+
+    #[test]
+    fn truncated_and_foreign_files_are_rejected() {
+        let particles: Vec<Particle> = vec![Particle::new([0.0; 3], [0.0; 3], 1.0, 0.0); 2];
+        let bytes: Vec<u8> = encode(0, 0.0, &particles);
+        for bytes in [&bytes[..bytes.len() - 1], &bytes[..10], &b"PNG\0".repeat(8)] {
+            let e: io::Error = decode(bytes).unwrap_err();
+            assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+        }
+    }
+}
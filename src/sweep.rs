@@ -0,0 +1,261 @@
+use {
+    crate::{
+        error::Error,
+        headless::{self, Output, Summary},
+        init_galaxy,
+        options::Options,
+        scenario::{self, ScenarioError, Setup},
+        tracked_indexes, GpuInfo, Particle, CALIBRATE,
+    },
+    serde::{Deserialize, Serialize},
+    std::{
+        collections::BTreeMap,
+        fmt, fs, io,
+        path::{Path, PathBuf},
+    },
+};
+
+// expands a base scenario over parameter values, substituted for ${name} in its files
+#[derive(Deserialize, Clone, Debug)]
+pub struct Sweep {
+    // relative to the sweep file
+    pub scenario: String,
+    #[serde(default)]
+    pub expand: Expand,
+    pub params: Vec<Param>,
+    // fall back to --steps and --snapshot-every
+    #[serde(default)]
+    pub steps: Option<u64>,
+    #[serde(default)]
+    pub snapshot_every: Option<u64>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct Param {
+    pub name: String,
+    pub values: Vec<f64>,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, Default)]
+pub enum Expand {
+    // every combination of values
+    #[default]
+    Grid,
+    // the i-th value of every param together, all lists have the same length
+    List,
+}
+
+#[derive(Serialize, Debug)]
+struct Manifest {
+    sweep: PathBuf,
+    scenario: PathBuf,
+    runs: Vec<Run>,
+}
+
+#[derive(Serialize, Debug)]
+struct Run {
+    dir: PathBuf,
+    params: BTreeMap<String, f64>,
+    particles: usize,
+    summary: Summary,
+}
+
+#[derive(Debug)]
+pub enum SweepError {
+    Io(PathBuf, io::Error),
+    Parse(PathBuf, ron::error::SpannedError),
+    Scenario(ScenarioError),
+    Length(String),
+    // a run that failed, in its directory
+    Run(PathBuf, Box<Error>),
+}
+
+impl fmt::Display for SweepError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SweepError::Io(path, e) => write!(f, "{}: {}", path.display(), e),
+            SweepError::Parse(path, e) => write!(f, "{}: {}", path.display(), e),
+            SweepError::Scenario(e) => write!(f, "{}", e),
+            SweepError::Length(name) => {
+                write!(f, "list sweep param {} has a different number of values", name)
+            }
+            SweepError::Run(dir, e) => write!(f, "{}: {}", dir.display(), e),
+        }
+    }
+}
+
+impl std::error::Error for SweepError {}
+
+impl From<ScenarioError> for SweepError {
+    fn from(e: ScenarioError) -> Self {
+        SweepError::Scenario(e)
+    }
+}
+
+impl Sweep {
+    // steps and snapshot_every are written without Some
+    fn parse(text: &str) -> Result<Self, ron::error::SpannedError> {
+        ron::Options::default()
+            .with_default_extension(ron::extensions::Extensions::IMPLICIT_SOME)
+            .from_str(text)
+    }
+
+    pub fn expand(&self) -> Result<Vec<Vec<(String, f64)>>, SweepError> {
+        match self.expand {
+            Expand::Grid => {
+                let mut runs: Vec<Vec<(String, f64)>> = vec![Vec::new()];
+                for param in &self.params {
+                    runs = runs
+                        .iter()
+                        .flat_map(|run| {
+                            param.values.iter().map(move |value| {
+                                let mut run: Vec<(String, f64)> = run.clone();
+                                run.push((param.name.clone(), *value));
+                                run
+                            })
+                        })
+                        .collect();
+                }
+                Ok(runs)
+            }
+            Expand::List => {
+                let len: usize = self.params.first().map_or(0, |p| p.values.len());
+                if let Some(param) = self.params.iter().find(|p| p.values.len() != len) {
+                    return Err(SweepError::Length(param.name.clone()));
+                }
+                Ok((0..len)
+                    .map(|i| {
+                        self.params
+                            .iter()
+                            .map(|p| (p.name.clone(), p.values[i]))
+                            .collect()
+                    })
+                    .collect())
+            }
+        }
+    }
+}
+
+fn write_manifest(path: &Path, manifest: &Manifest) -> Result<(), SweepError> {
+    let file: fs::File = fs::File::create(path).map_err(|e| SweepError::Io(path.into(), e))?;
+    serde_json::to_writer_pretty(file, manifest)
+        .map_err(|e| SweepError::Io(path.into(), e.into()))
+}
+
+// runs every expanded scenario through the headless backend, one after another,
+// each into its own directory under --out
+pub async fn run(path: &Path, options: &Options) -> Result<(), SweepError> {
+    let text: String = fs::read_to_string(path).map_err(|e| SweepError::Io(path.into(), e))?;
+    let sweep: Sweep = Sweep::parse(&text).map_err(|e| SweepError::Parse(path.into(), e))?;
+    let scenario: PathBuf = path
+        .parent()
+        .unwrap_or(Path::new(""))
+        .join(&sweep.scenario);
+    let out: PathBuf = options.out.clone().unwrap_or_else(|| PathBuf::from("sweep"));
+    fs::create_dir_all(&out).map_err(|e| SweepError::Io(out.clone(), e))?;
+
+    let runs: Vec<Vec<(String, f64)>> = sweep.expand()?;
+    let mut manifest: Manifest = Manifest {
+        sweep: path.into(),
+        scenario: scenario.clone(),
+        runs: Vec::new(),
+    };
+    for (i, params) in runs.iter().enumerate() {
+        let vars: Vec<(String, String)> = params
+            .iter()
+            .map(|(name, value)| (name.clone(), value.to_string()))
+            .collect();
+        println!("run {}/{} {:?}", i + 1, runs.len(), params);
+        let setup: Setup = scenario::load(&scenario, &vars)?;
+        let particles: Vec<Particle> = init_galaxy(CALIBRATE, setup.galaxies, setup.seed);
+        let indexes: Vec<usize> = tracked_indexes(&particles, setup.seed);
+        let dir: PathBuf = out.join(format!("run_{:03}", i));
+        let output: Output = Output {
+            steps: sweep.steps.unwrap_or(options.steps),
+            snapshot_every: sweep.snapshot_every.unwrap_or(options.snapshot_every),
+            dir: Some(dir.clone()),
+        };
+        let count: usize = particles.len();
+        let summary: Summary = headless::run(GpuInfo::new(count as u32), particles, indexes, &output, options)
+            .await
+            .map_err(|e| SweepError::Run(dir.clone(), Box::new(e)))?;
+        manifest.runs.push(Run {
+            dir,
+            params: params.iter().cloned().collect(),
+            particles: count,
+            summary,
+        });
+        // rewritten after every run so an interrupted sweep keeps its finished runs
+        write_manifest(&out.join("manifest.json"), &manifest)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand(expand: &str, params: &str) -> Result<Vec<Vec<(String, f64)>>, SweepError> {
+        let text: String = format!(
+            "Sweep(scenario: \"base.ron\", expand: {}, params: [{}])",
+            expand, params
+        );
+        Sweep::parse(&text).unwrap().expand()
+    }
+
+    fn run(values: &[(&str, f64)]) -> Vec<(String, f64)> {
+        values
+            .iter()
+            .map(|(name, value)| (name.to_string(), *value))
+            .collect()
+    }
+
+    const AB: &str = "Param(name: \"a\", values: [1, 2]), Param(name: \"b\", values: [3, 4, 5])";
+
+    #[test]
+    fn grid_is_every_combination_last_param_fastest() {
+        assert_eq!(
+            expand("Grid", AB).unwrap(),
+            vec![
+                run(&[("a", 1.0), ("b", 3.0)]),
+                run(&[("a", 1.0), ("b", 4.0)]),
+                run(&[("a", 1.0), ("b", 5.0)]),
+                run(&[("a", 2.0), ("b", 3.0)]),
+                run(&[("a", 2.0), ("b", 4.0)]),
+                run(&[("a", 2.0), ("b", 5.0)]),
+            ]
+        );
+    }
+
+    #[test]
+    fn grid_without_params_is_one_run() {
+        assert_eq!(expand("Grid", "").unwrap(), vec![run(&[])]);
+    }
+
+    #[test]
+    fn list_pairs_values_by_position() {
+        let params: &str = "Param(name: \"a\", values: [1, 2]), Param(name: \"b\", values: [3, 4])";
+        assert_eq!(
+            expand("List", params).unwrap(),
+            vec![
+                run(&[("a", 1.0), ("b", 3.0)]),
+                run(&[("a", 2.0), ("b", 4.0)])
+            ]
+        );
+    }
+
+    #[test]
+    fn list_lengths_must_match() {
+        match expand("List", AB) {
+            Err(SweepError::Length(name)) => assert_eq!(name, "b"),
+            runs => panic!("{:?}", runs),
+        }
+    }
+
+    #[test]
+    fn steps_need_no_some() {
+        let sweep: Sweep =
+            Sweep::parse("Sweep(scenario: \"base.ron\", params: [], steps: 10)").unwrap();
+        assert_eq!((sweep.steps, sweep.snapshot_every), (Some(10), None));
+    }
+}